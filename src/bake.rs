@@ -0,0 +1,240 @@
+use palette::LinSrgba;
+
+use crate::readback::ImageBuffer;
+use crate::state::{RenderError, State};
+
+/// Frames between progress lines, a multiple of `CHECK_INTERVAL`.
+const PROGRESS_INTERVAL: u32 = 16;
+/// Frames between readbacks of the radiance to check for convergence, as reading it back
+/// stalls the GPU.
+const CHECK_INTERVAL: u32 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BakeSettings {
+    /// Converged once the frames since the last check change the accumulated radiance by less
+    /// than this each on average, relative to its root mean square.
+    pub tolerance: f32,
+    /// Frames accumulated before checking for convergence, so the jitter covers some angles.
+    /// Convergence is only checked every `CHECK_INTERVAL` frames, so it may take a few more.
+    pub min_frames: u32,
+    /// Frames after which the bake stops even if it hasn't converged.
+    pub max_frames: u32,
+}
+
+impl Default for BakeSettings {
+    fn default() -> Self {
+        Self {
+            tolerance: 1e-4,
+            min_frames: 16,
+            max_frames: 4096,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BakeReport {
+    pub frames: u32,
+    /// Relative difference made by each frame since the last check, on average.
+    pub difference: f32,
+    pub converged: bool,
+}
+
+fn squared_length(color: [f32; 3]) -> f64 {
+    color.iter().map(|&x| (x as f64).powi(2)).sum()
+}
+
+/// Root mean square of the difference between the colors of `a` and `b`, relative to the root
+/// mean square of `b`. Alpha is ignored.
+pub fn relative_difference(a: &ImageBuffer<LinSrgba>, b: &ImageBuffer<LinSrgba>) -> f32 {
+    let (difference, magnitude) =
+        a.pixels
+            .iter()
+            .zip(&b.pixels)
+            .fold((0.0, 0.0), |(difference, magnitude), (a, b)| {
+                (
+                    difference
+                        + squared_length([a.red - b.red, a.green - b.green, a.blue - b.blue]),
+                    magnitude + squared_length([b.red, b.green, b.blue]),
+                )
+            });
+    if magnitude == 0.0 {
+        // A black scene converges as soon as it stays black.
+        return if difference == 0.0 {
+            0.0
+        } else {
+            f32::INFINITY
+        };
+    }
+    (difference / magnitude).sqrt() as f32
+}
+
+/// Accumulates frames until the radiance stops changing or `settings.max_frames` is reached,
+/// reporting progress on stdout. The state should accumulate with jittered directions and no
+/// temporal blend, so the result is the average over all frames.
+pub fn bake(
+    state: &mut State,
+    settings: &BakeSettings,
+) -> Result<(ImageBuffer<LinSrgba>, BakeReport), RenderError> {
+    converge(settings, state, State::render, State::read_radiance)
+}
+
+/// Renders frames of `state` until the radiance read from it converges. The radiance is only
+/// read every `CHECK_INTERVAL` frames, and after the last one.
+fn converge<S, E>(
+    settings: &BakeSettings,
+    state: &mut S,
+    render: impl Fn(&mut S) -> Result<(), E>,
+    read: impl Fn(&S) -> ImageBuffer<LinSrgba>,
+) -> Result<(ImageBuffer<LinSrgba>, BakeReport), E> {
+    render(state)?;
+    let mut radiance = read(state);
+    let mut checked_frames = 1;
+    let mut report = BakeReport {
+        frames: 1,
+        difference: f32::INFINITY,
+        converged: false,
+    };
+
+    while report.frames < settings.max_frames {
+        render(state)?;
+        report.frames += 1;
+        if report.frames % CHECK_INTERVAL != 0 && report.frames < settings.max_frames {
+            continue;
+        }
+        let next_radiance = read(state);
+        report.difference = relative_difference(&next_radiance, &radiance)
+            / (report.frames - checked_frames) as f32;
+        radiance = next_radiance;
+        checked_frames = report.frames;
+
+        report.converged =
+            report.frames >= settings.min_frames && report.difference < settings.tolerance;
+        if report.converged {
+            break;
+        }
+        if report.frames % PROGRESS_INTERVAL == 0 {
+            println!(
+                "Frame {}/{}: difference {:.3e}",
+                report.frames, settings.max_frames, report.difference
+            );
+        }
+    }
+    Ok((radiance, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::vector;
+    use std::cell::Cell;
+
+    fn image(value: f32) -> ImageBuffer<LinSrgba> {
+        ImageBuffer {
+            size: vector![2, 1],
+            pixels: vec![LinSrgba::new(value, value, value, 1.0); 2],
+        }
+    }
+
+    fn render(frames: &mut u32) -> Result<(), ()> {
+        *frames += 1;
+        Ok(())
+    }
+
+    /// Frames alternating around 1 by `1 / i`, so they settle on it.
+    fn settling_frame(&frame: &u32) -> ImageBuffer<LinSrgba> {
+        let sign = if frame % 2 == 0 { 1.0 } else { -1.0 };
+        image(1.0 + sign / frame as f32)
+    }
+
+    #[test]
+    fn relative_differences() {
+        assert_eq!(relative_difference(&image(1.0), &image(1.0)), 0.0);
+        assert!((relative_difference(&image(1.1), &image(1.0)) - 0.1).abs() < 1e-6);
+        assert_eq!(relative_difference(&image(0.0), &image(0.0)), 0.0);
+        assert_eq!(relative_difference(&image(1.0), &image(0.0)), f32::INFINITY);
+    }
+
+    #[test]
+    fn converges_after_min_frames() {
+        let settings = BakeSettings {
+            tolerance: 1e-3,
+            min_frames: 20,
+            max_frames: 100,
+        };
+        let (_, report) = converge(&settings, &mut 0, render, |_| image(0.5)).unwrap();
+        // The first check at or after the minimum.
+        assert_eq!(report, BakeReport {
+            frames: 24,
+            difference: 0.0,
+            converged: true,
+        });
+    }
+
+    #[test]
+    fn converges_once_frames_stop_changing() {
+        let settings = BakeSettings {
+            tolerance: 0.01,
+            min_frames: 2,
+            max_frames: 100,
+        };
+        let (radiance, report) = converge(&settings, &mut 0, render, settling_frame).unwrap();
+        assert!(report.converged);
+        assert!(report.difference < settings.tolerance);
+        // From frame 8 to 16, it changes by 1/16, or less than 0.01 a frame.
+        assert_eq!(report.frames, 16);
+        assert_eq!(radiance.pixels[0].red, 1.0 + 1.0 / 16.0);
+    }
+
+    #[test]
+    fn reads_back_only_at_checks() {
+        let settings = BakeSettings {
+            tolerance: 1e-6,
+            min_frames: 2,
+            max_frames: 20,
+        };
+        let reads = Cell::new(Vec::new());
+        let read = |&frame: &u32| {
+            let mut frames = reads.take();
+            frames.push(frame);
+            reads.set(frames);
+            settling_frame(&frame)
+        };
+        let (radiance, report) = converge(&settings, &mut 0, render, read).unwrap();
+        assert!(!report.converged);
+        assert_eq!(report.frames, 20);
+        // The first frame, every check, and the last frame.
+        assert_eq!(reads.take(), vec![1, 8, 16, 20]);
+        assert_eq!(radiance.pixels[0].red, 1.0 + 1.0 / 20.0);
+    }
+
+    #[test]
+    fn stops_at_max_frames() {
+        let settings = BakeSettings {
+            tolerance: 1e-6,
+            min_frames: 2,
+            max_frames: 10,
+        };
+        let (_, report) = converge(&settings, &mut 0, render, settling_frame).unwrap();
+        assert!(!report.converged);
+        assert_eq!(report.frames, 10);
+        assert!(report.difference > settings.tolerance);
+    }
+
+    #[test]
+    fn stops_on_errors() {
+        let result = converge(
+            &BakeSettings::default(),
+            &mut 0,
+            |frames: &mut u32| {
+                *frames += 1;
+                if *frames < 3 {
+                    Ok(())
+                } else {
+                    Err("lost device")
+                }
+            },
+            |_| image(1.0),
+        );
+        assert_eq!(result.unwrap_err(), "lost device");
+    }
+}
@@ -0,0 +1,217 @@
+use nalgebra::Vector2;
+use palette::{LinSrgb, LinSrgba, Srgba};
+use std::fmt::{self, Display, Formatter};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use crate::readback::ImageBuffer;
+
+/// Normal map texels shorter than this have no normal, so the flat gray of an unpainted normal
+/// map doesn't dim the emission.
+const MIN_NORMAL_LENGTH: f32 = 0.1;
+
+#[derive(Debug)]
+pub enum BitmapError {
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    Png {
+        path: PathBuf,
+        error: png::DecodingError,
+    },
+    UnsupportedFormat {
+        path: PathBuf,
+    },
+    SizeMismatch {
+        path: PathBuf,
+        expected: Vector2<u32>,
+        size: Vector2<u32>,
+    },
+}
+
+impl Display for BitmapError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            BitmapError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            BitmapError::Png { path, error } => write!(f, "{}: {}", path.display(), error),
+            BitmapError::UnsupportedFormat { path } => {
+                write!(f, "{}: unsupported PNG format", path.display())
+            }
+            BitmapError::SizeMismatch {
+                path,
+                expected,
+                size,
+            } => write!(
+                f,
+                "{}: expected {}x{} like the albedo, got {}x{}",
+                path.display(),
+                expected.x,
+                expected.y,
+                size.x,
+                size.y
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BitmapError {}
+
+/// A scene given as texels instead of triangles. It goes straight into the prerender textures,
+/// one world-aligned texel at a time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bitmap {
+    pub size: Vector2<u32>,
+    /// World position of the bottom left corner.
+    pub origin: Vector2<f32>,
+    /// World size of a texel.
+    pub texel_size: f32,
+    /// Row-major, starting at the top left like images.
+    pub albedo: Vec<LinSrgba>,
+    pub emission: Vec<LinSrgb>,
+    /// Zero where the texel has no normal.
+    pub normal: Vec<Vector2<f32>>,
+}
+
+impl Bitmap {
+    /// A fully transparent bitmap.
+    pub fn new(size: Vector2<u32>, origin: Vector2<f32>, texel_size: f32) -> Self {
+        let len = (size.x * size.y) as usize;
+        Self {
+            size,
+            origin,
+            texel_size,
+            albedo: vec![LinSrgba::new(0.0, 0.0, 0.0, 0.0); len],
+            emission: vec![LinSrgb::new(0.0, 0.0, 0.0); len],
+            normal: vec![Vector2::zeros(); len],
+        }
+    }
+
+    /// Builds a bitmap from PNG images the size of the albedo. The emission is scaled by
+    /// `emission_intensity`. The normal image stores x and y in red and green, from 0 for -1 to
+    /// 255 for 1, with y up.
+    pub fn load(
+        albedo: &Path,
+        emission: Option<(&Path, f32)>,
+        normal: Option<&Path>,
+        origin: Vector2<f32>,
+        texel_size: f32,
+    ) -> Result<Self, BitmapError> {
+        let albedo_image = read_png(albedo)?;
+        let mut bitmap = Self::new(albedo_image.size, origin, texel_size);
+        bitmap.albedo = albedo_image
+            .pixels
+            .iter()
+            .map(|pixel| pixel.into_format().into_linear())
+            .collect();
+
+        let read_sized = |path: &Path| {
+            let image = read_png(path)?;
+            if image.size != bitmap.size {
+                return Err(BitmapError::SizeMismatch {
+                    path: path.to_path_buf(),
+                    expected: bitmap.size,
+                    size: image.size,
+                });
+            }
+            Ok(image.pixels)
+        };
+        if let Some((path, intensity)) = emission {
+            let emission = read_sized(path)?
+                .iter()
+                .map(|pixel| pixel.color.into_format().into_linear() * intensity)
+                .collect();
+            bitmap.emission = emission;
+        }
+        if let Some(path) = normal {
+            let normal = read_sized(path)?
+                .iter()
+                .map(|pixel| {
+                    let normal = Vector2::new(pixel.red, pixel.green).map(|x| x as f32) / 127.5
+                        - Vector2::repeat(1.0);
+                    if normal.norm() < MIN_NORMAL_LENGTH {
+                        Vector2::zeros()
+                    } else {
+                        normal.normalize()
+                    }
+                })
+                .collect();
+            bitmap.normal = normal;
+        }
+        Ok(bitmap)
+    }
+
+    /// Sets the normals of the texels that aren't transparent to point down the slope of the
+    /// alpha, out of the opaque parts. Texels within flat alpha get none.
+    pub fn derive_normals(&mut self) {
+        let size = self.size.cast::<i64>();
+        let alpha = |x: i64, y: i64| {
+            // Clamp to the edge, so the border doesn't look like an edge.
+            let (x, y) = (x.clamp(0, size.x - 1), y.clamp(0, size.y - 1));
+            self.albedo[(y * size.x + x) as usize].alpha
+        };
+        let normal: Vec<_> = (0..size.y)
+            .flat_map(|y| (0..size.x).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                if alpha(x, y) == 0.0 {
+                    return Vector2::zeros();
+                }
+                // Sobel, with rows going down but y going up.
+                let column = |x| alpha(x, y - 1) + 2.0 * alpha(x, y) + alpha(x, y + 1);
+                let row = |y| alpha(x - 1, y) + 2.0 * alpha(x, y) + alpha(x + 1, y);
+                let gradient = Vector2::new(column(x + 1) - column(x - 1), row(y - 1) - row(y + 1));
+                if gradient.norm() < f32::EPSILON {
+                    Vector2::zeros()
+                } else {
+                    -gradient.normalize()
+                }
+            })
+            .collect();
+        self.normal = normal;
+    }
+}
+
+/// Reads an 8-bit sRGB image, with or without alpha.
+fn read_png(path: &Path) -> Result<ImageBuffer<Srgba<u8>>, BitmapError> {
+    let png_error = |error| BitmapError::Png {
+        path: path.to_path_buf(),
+        error,
+    };
+    let file = File::open(path).map_err(|error| BitmapError::Io {
+        path: path.to_path_buf(),
+        error,
+    })?;
+    let mut decoder = png::Decoder::new(BufReader::new(file));
+    // Palettes and low bit depths expand to 8 bits, 16 bits are cut down to 8.
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(png_error)?;
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data).map_err(png_error)?;
+    let data = &data[..info.buffer_size()];
+
+    let pixels = match info.color_type {
+        png::ColorType::Grayscale => data.iter().map(|&v| Srgba::new(v, v, v, 255)).collect(),
+        png::ColorType::GrayscaleAlpha => data
+            .chunks_exact(2)
+            .map(|p| Srgba::new(p[0], p[0], p[0], p[1]))
+            .collect(),
+        png::ColorType::Rgb => data
+            .chunks_exact(3)
+            .map(|p| Srgba::new(p[0], p[1], p[2], 255))
+            .collect(),
+        png::ColorType::Rgba => data
+            .chunks_exact(4)
+            .map(|p| Srgba::new(p[0], p[1], p[2], p[3]))
+            .collect(),
+        png::ColorType::Indexed => {
+            return Err(BitmapError::UnsupportedFormat {
+                path: path.to_path_buf(),
+            })
+        }
+    };
+    Ok(ImageBuffer {
+        size: Vector2::new(info.width, info.height),
+        pixels,
+    })
+}
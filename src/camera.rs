@@ -0,0 +1,50 @@
+use nalgebra::{Rotation2, Vector2};
+
+/// Lowest and highest zoom, in view pixels per world pixel.
+const MIN_ZOOM: f32 = 1.0 / 64.0;
+const MAX_ZOOM: f32 = 64.0;
+
+/// A 2D view of the world. View coordinates are logical pixels from the bottom left of the view,
+/// like world coordinates, which they match when the camera is centered on the view with no zoom
+/// or rotation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    /// The world point at the center of the view.
+    pub position: Vector2<f32>,
+    pub zoom: f32,
+    /// Counterclockwise rotation of the camera, in radians. The world appears rotated the other
+    /// way.
+    pub rotation: f32,
+}
+
+impl Camera {
+    /// A camera showing the world from its origin at the bottom left, as if there was none.
+    pub fn new(view_size: Vector2<f32>) -> Self {
+        Self {
+            position: view_size / 2.0,
+            zoom: 1.0,
+            rotation: 0.0,
+        }
+    }
+
+    pub fn view_to_world(&self, point: Vector2<f32>, view_size: Vector2<f32>) -> Vector2<f32> {
+        self.position + Rotation2::new(self.rotation) * (point - view_size / 2.0) / self.zoom
+    }
+
+    /// Moves the world by `delta` view pixels, so that dragged points stay under the cursor.
+    pub fn pan(&mut self, delta: Vector2<f32>) {
+        self.position -= Rotation2::new(self.rotation) * delta / self.zoom;
+    }
+
+    /// Multiplies the zoom by `factor`, keeping the world point under the view point `anchor`
+    /// in place.
+    pub fn zoom_at(&mut self, anchor: Vector2<f32>, factor: f32, view_size: Vector2<f32>) {
+        let world_anchor = self.view_to_world(anchor, view_size);
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.position += world_anchor - self.view_to_world(anchor, view_size);
+    }
+
+    pub fn rotate(&mut self, angle: f32) {
+        self.rotation = (self.rotation + angle).rem_euclid(std::f32::consts::TAU);
+    }
+}
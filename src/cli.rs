@@ -0,0 +1,561 @@
+use nalgebra::{vector, Vector2};
+use std::fmt::{self, Display, Formatter};
+use std::path::PathBuf;
+use std::str::FromStr;
+use wgpu::{PowerPreference, PresentMode};
+
+use crate::bake::BakeSettings;
+use crate::state::exposure::AutoExposureSettings;
+use crate::state::radiance::RadianceSettings;
+use crate::state::render::{RenderSettings, ToneMapping, Upsampling};
+use crate::state::{DeviceSettings, MIN_RENDER_SCALE};
+use crate::svg;
+
+pub const DEFAULT_SCENE: &str = "scenes/default.ron";
+/// Resolution of headless renders when `--size` isn't given.
+const DEFAULT_OUTPUT_SIZE: Vector2<u32> = Vector2::new(800, 600);
+
+pub const USAGE: &str = "\
+Usage: radiance-singlegrid-render [OPTIONS] [SCENE]
+       radiance-singlegrid-render bake [OPTIONS] --output <PATH> [SCENE]
+
+Renders SCENE (default: scenes/default.ron) in a window, or to files with --output.
+SCENE is a RON scene, or an SVG file whose shapes are imported into an empty scene.
+The bake command accumulates jittered frames until the radiance converges and writes it
+to PATH.pfm.
+
+Options:
+  --size <WIDTH>x<HEIGHT>    Window or output resolution [default output: 800x600]
+  --directions <N>           Light directions traced per frame [default: 32]
+  --bounces <N>              Light bounces per frame [default: 3]
+  --accumulate               Blend frames into a temporal history
+  --blend <WEIGHT>           Minimum weight of a new frame when accumulating [default: 0.05]
+  --jitter                   Rotate the light directions every frame
+  --present-mode <MODE>      fifo, mailbox or immediate [default: fifo]
+  --adapter <PREFERENCE>     high-performance, low-power or fallback [default: high-performance]
+  --tone-mapping <CURVE>     linear, reinhard, aces or agx [default: aces]
+  --exposure <EV>            Exposure in stops, or compensation with --auto-exposure [default: 0]
+  --auto-exposure            Adapt the exposure to the image brightness
+  --render-scale <SCALE>     Fraction of the window or output resolution to render at [default: 1]
+  --upsampling <FILTER>      nearest, bilinear or edge-aware [default: edge-aware]
+  --output <PATH>            Render without a window and write PATH.pfm and PATH.png
+  --frames <N>               Frames to render before writing --output [default: 1]
+  --curve-tolerance <PIXELS> How far flattened SVG curves may stray from the real ones [default: 0.25]
+  --save-scene <PATH>        Write the scene as RON to PATH instead of rendering it
+  --compare-reference        Check the last --output frame against the CPU reference solver
+  -h, --help                 Print this help
+
+Bake options:
+  --tolerance <DIFFERENCE>   Relative change of a frame counted as converged [default: 1e-4]
+  --min-frames <N>           Frames accumulated before checking convergence [default: 16]
+  --max-frames <N>           Frames after which to stop anyway [default: 4096]
+";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CliError {
+    UnknownOption(String),
+    MissingValue(&'static str),
+    InvalidValue {
+        option: &'static str,
+        value: String,
+        expected: &'static str,
+    },
+    UnexpectedArgument(String),
+    Conflict(&'static str),
+    OnlyForBake(&'static str),
+}
+
+impl Display for CliError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::UnknownOption(option) => write!(f, "unknown option {}", option),
+            CliError::MissingValue(option) => write!(f, "{} needs a value", option),
+            CliError::InvalidValue {
+                option,
+                value,
+                expected,
+            } => write!(
+                f,
+                "invalid value {:?} for {}, expected {}",
+                value, option, expected
+            ),
+            CliError::UnexpectedArgument(argument) => {
+                write!(
+                    f,
+                    "unexpected argument {:?}, only one scene can be given",
+                    argument
+                )
+            }
+            CliError::Conflict(reason) => write!(f, "{}", reason),
+            CliError::OnlyForBake(option) => write!(f, "{} is only used by bake", option),
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+#[derive(Debug, Clone)]
+pub struct Args {
+    pub help: bool,
+    pub scene: PathBuf,
+    /// How closely SVG curves are followed, see `Scene::load`.
+    pub curve_tolerance: f32,
+    pub size: Option<Vector2<u32>>,
+    pub radiance_settings: RadianceSettings,
+    pub render_settings: RenderSettings,
+    pub device_settings: DeviceSettings,
+    pub output: Option<PathBuf>,
+    pub frames: u32,
+    /// Write the loaded scene here instead of rendering, to convert SVG scenes to RON.
+    pub save_scene: Option<PathBuf>,
+    /// Print how far the GPU radiance of the last frame is from the CPU reference solver.
+    pub compare_reference: bool,
+    /// Set by the `bake` command, which always has an output.
+    pub bake: Option<BakeSettings>,
+}
+
+fn parse_number<T: FromStr>(option: &'static str, value: &str) -> Result<T, CliError> {
+    value.parse().map_err(|_| CliError::InvalidValue {
+        option,
+        value: value.to_string(),
+        expected: "a number",
+    })
+}
+
+/// Parses a count of something there must be at least one of.
+fn parse_count(option: &'static str, value: &str) -> Result<u32, CliError> {
+    match value.parse() {
+        Ok(count) if count > 0 => Ok(count),
+        _ => Err(CliError::InvalidValue {
+            option,
+            value: value.to_string(),
+            expected: "a whole number above 0",
+        }),
+    }
+}
+
+fn parse_size(value: &str) -> Result<Vector2<u32>, CliError> {
+    let invalid = || CliError::InvalidValue {
+        option: "--size",
+        value: value.to_string(),
+        expected: "<WIDTH>x<HEIGHT> with both above 0",
+    };
+    let (width, height) = value.split_once('x').ok_or_else(invalid)?;
+    match (width.parse(), height.parse()) {
+        (Ok(width), Ok(height)) if width > 0 && height > 0 => Ok(vector![width, height]),
+        _ => Err(invalid()),
+    }
+}
+
+fn parse_choice<T: Copy>(
+    option: &'static str,
+    value: &str,
+    choices: &[(&str, T)],
+    expected: &'static str,
+) -> Result<T, CliError> {
+    choices
+        .iter()
+        .find(|(name, _)| *name == value)
+        .map(|(_, choice)| *choice)
+        .ok_or_else(|| CliError::InvalidValue {
+            option,
+            value: value.to_string(),
+            expected,
+        })
+}
+
+impl Args {
+    /// Parses the arguments after the program name. Checks that don't need a device are done
+    /// here; the rest is left to `RadianceSettings::validate`.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, CliError> {
+        let mut parsed = Args {
+            help: false,
+            scene: PathBuf::from(DEFAULT_SCENE),
+            curve_tolerance: svg::DEFAULT_TOLERANCE,
+            size: None,
+            radiance_settings: RadianceSettings {
+                light_directions: 32,
+                light_bounces: 3,
+                temporal_accumulate: false,
+                temporal_blend: 0.05,
+                direction_jitter: false,
+            },
+            render_settings: RenderSettings::default(),
+            device_settings: DeviceSettings::default(),
+            output: None,
+            frames: 1,
+            save_scene: None,
+            compare_reference: false,
+            bake: None,
+        };
+        let mut scene = None;
+        let mut present_mode = None;
+        let mut frames = None;
+        let mut temporal_blend = None;
+        let mut bake_settings = BakeSettings::default();
+        // The first bake option given, to reject it outside of bake.
+        let mut bake_option = None;
+
+        let mut args = args.into_iter().peekable();
+        let is_bake = args.next_if(|arg| arg == "bake").is_some();
+        while let Some(arg) = args.next() {
+            let mut value =
+                |option: &'static str| args.next().ok_or(CliError::MissingValue(option));
+            match arg.as_str() {
+                "-h" | "--help" => parsed.help = true,
+                "--size" => parsed.size = Some(parse_size(&value("--size")?)?),
+                "--directions" => {
+                    parsed.radiance_settings.light_directions =
+                        parse_count("--directions", &value("--directions")?)?
+                }
+                "--bounces" => {
+                    parsed.radiance_settings.light_bounces =
+                        parse_count("--bounces", &value("--bounces")?)?
+                }
+                "--accumulate" => parsed.radiance_settings.temporal_accumulate = true,
+                "--blend" => temporal_blend = Some(parse_number("--blend", &value("--blend")?)?),
+                "--jitter" => parsed.radiance_settings.direction_jitter = true,
+                "--present-mode" => {
+                    present_mode = Some(parse_choice(
+                        "--present-mode",
+                        &value("--present-mode")?,
+                        &[
+                            ("fifo", PresentMode::Fifo),
+                            ("mailbox", PresentMode::Mailbox),
+                            ("immediate", PresentMode::Immediate),
+                        ],
+                        "fifo, mailbox or immediate",
+                    )?)
+                }
+                "--adapter" => {
+                    let (power_preference, force_fallback_adapter) = parse_choice(
+                        "--adapter",
+                        &value("--adapter")?,
+                        &[
+                            (
+                                "high-performance",
+                                (PowerPreference::HighPerformance, false),
+                            ),
+                            ("low-power", (PowerPreference::LowPower, false)),
+                            ("fallback", (PowerPreference::default(), true)),
+                        ],
+                        "high-performance, low-power or fallback",
+                    )?;
+                    parsed.device_settings.power_preference = power_preference;
+                    parsed.device_settings.force_fallback_adapter = force_fallback_adapter;
+                }
+                "--tone-mapping" => {
+                    parsed.render_settings.tone_mapping = parse_choice(
+                        "--tone-mapping",
+                        &value("--tone-mapping")?,
+                        &[
+                            ("linear", ToneMapping::Linear),
+                            ("reinhard", ToneMapping::Reinhard),
+                            ("aces", ToneMapping::Aces),
+                            ("agx", ToneMapping::Agx),
+                        ],
+                        "linear, reinhard, aces or agx",
+                    )?
+                }
+                "--exposure" => {
+                    parsed.render_settings.exposure =
+                        parse_number("--exposure", &value("--exposure")?)?
+                }
+                "--auto-exposure" => {
+                    parsed.render_settings.auto_exposure = Some(AutoExposureSettings::default())
+                }
+                "--render-scale" => {
+                    let render_scale: f32 =
+                        parse_number("--render-scale", &value("--render-scale")?)?;
+                    if !(MIN_RENDER_SCALE..=1.0).contains(&render_scale) {
+                        return Err(CliError::InvalidValue {
+                            option: "--render-scale",
+                            value: render_scale.to_string(),
+                            expected: "a number from 0.0625 to 1",
+                        });
+                    }
+                    parsed.render_settings.render_scale = render_scale;
+                }
+                "--upsampling" => {
+                    parsed.render_settings.upsampling = parse_choice(
+                        "--upsampling",
+                        &value("--upsampling")?,
+                        &[
+                            ("nearest", Upsampling::Nearest),
+                            ("bilinear", Upsampling::Bilinear),
+                            ("edge-aware", Upsampling::EdgeAware),
+                        ],
+                        "nearest, bilinear or edge-aware",
+                    )?
+                }
+                "--output" => parsed.output = Some(PathBuf::from(value("--output")?)),
+                "--frames" => frames = Some(parse_number("--frames", &value("--frames")?)?),
+                "--curve-tolerance" => {
+                    let tolerance: f32 =
+                        parse_number("--curve-tolerance", &value("--curve-tolerance")?)?;
+                    if !(tolerance > 0.0 && tolerance.is_finite()) {
+                        return Err(CliError::InvalidValue {
+                            option: "--curve-tolerance",
+                            value: tolerance.to_string(),
+                            expected: "a distance above 0",
+                        });
+                    }
+                    parsed.curve_tolerance = tolerance;
+                }
+                "--save-scene" => parsed.save_scene = Some(PathBuf::from(value("--save-scene")?)),
+                "--compare-reference" => parsed.compare_reference = true,
+                "--tolerance" => {
+                    bake_option.get_or_insert("--tolerance");
+                    let tolerance: f32 = parse_number("--tolerance", &value("--tolerance")?)?;
+                    if !(tolerance > 0.0 && tolerance.is_finite()) {
+                        return Err(CliError::InvalidValue {
+                            option: "--tolerance",
+                            value: tolerance.to_string(),
+                            expected: "a difference above 0",
+                        });
+                    }
+                    bake_settings.tolerance = tolerance;
+                }
+                "--min-frames" => {
+                    bake_option.get_or_insert("--min-frames");
+                    bake_settings.min_frames =
+                        parse_number("--min-frames", &value("--min-frames")?)?
+                }
+                "--max-frames" => {
+                    bake_option.get_or_insert("--max-frames");
+                    bake_settings.max_frames =
+                        parse_number("--max-frames", &value("--max-frames")?)?
+                }
+                option if option.starts_with('-') => {
+                    return Err(CliError::UnknownOption(option.to_string()))
+                }
+                _ if scene.is_some() => return Err(CliError::UnexpectedArgument(arg)),
+                _ => scene = Some(PathBuf::from(arg)),
+            }
+        }
+
+        if let Some(scene) = scene {
+            parsed.scene = scene;
+        }
+        if let Some(present_mode) = present_mode {
+            if parsed.output.is_some() {
+                return Err(CliError::Conflict(
+                    "--present-mode has no effect with --output, nothing is presented",
+                ));
+            }
+            parsed.device_settings.present_mode = present_mode;
+        }
+        if is_bake {
+            if parsed.output.is_none() {
+                return Err(CliError::Conflict("bake needs --output"));
+            }
+            if frames.is_some() {
+                return Err(CliError::Conflict(
+                    "bake renders until it converges, use --max-frames instead of --frames",
+                ));
+            }
+            if parsed.compare_reference {
+                return Err(CliError::Conflict(
+                    "--compare-reference checks a single frame, it can't be used with bake",
+                ));
+            }
+            if bake_settings.max_frames == 0 {
+                return Err(CliError::Conflict("--max-frames must be at least 1"));
+            }
+            if bake_settings.min_frames > bake_settings.max_frames {
+                return Err(CliError::Conflict(
+                    "--min-frames can't be above --max-frames",
+                ));
+            }
+            // Average every frame with jittered directions to cover all angles.
+            parsed.radiance_settings.temporal_accumulate = true;
+            parsed.radiance_settings.direction_jitter = true;
+            parsed.radiance_settings.temporal_blend = temporal_blend.unwrap_or(0.0);
+            parsed.bake = Some(bake_settings);
+        } else if let Some(option) = bake_option {
+            return Err(CliError::OnlyForBake(option));
+        }
+        if let Some(temporal_blend) = temporal_blend {
+            parsed.radiance_settings.temporal_blend = temporal_blend;
+        }
+        if parsed.save_scene.is_some() && parsed.output.is_some() {
+            return Err(CliError::Conflict(
+                "--save-scene doesn't render, it can't be used with --output or bake",
+            ));
+        }
+        if parsed.compare_reference && parsed.output.is_none() {
+            return Err(CliError::Conflict("--compare-reference needs --output"));
+        }
+        if let Some(frames) = frames {
+            if parsed.output.is_none() {
+                return Err(CliError::Conflict("--frames needs --output"));
+            }
+            if frames == 0 {
+                return Err(CliError::Conflict("--frames must be at least 1"));
+            }
+            parsed.frames = frames;
+        }
+        Ok(parsed)
+    }
+
+    /// The resolution of headless renders.
+    pub fn output_size(&self) -> Vector2<u32> {
+        self.size.unwrap_or(DEFAULT_OUTPUT_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, CliError> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn defaults() {
+        let args = parse(&[]).unwrap();
+        assert!(!args.help);
+        assert_eq!(args.scene, PathBuf::from(DEFAULT_SCENE));
+        assert_eq!(args.output_size(), DEFAULT_OUTPUT_SIZE);
+        assert_eq!(args.radiance_settings.light_directions, 32);
+        assert_eq!(args.radiance_settings.light_bounces, 3);
+        assert!(!args.radiance_settings.temporal_accumulate);
+        assert!(!args.radiance_settings.direction_jitter);
+        assert_eq!(args.render_settings.render_scale, 1.0);
+        assert_eq!(args.device_settings.present_mode, PresentMode::Fifo);
+        assert_eq!(args.curve_tolerance, svg::DEFAULT_TOLERANCE);
+        assert_eq!(args.output, None);
+        assert_eq!(args.frames, 1);
+        assert_eq!(args.save_scene, None);
+        assert!(!args.compare_reference);
+        assert_eq!(args.bake, None);
+    }
+
+    #[test]
+    fn options() {
+        let args = parse(&[
+            "--size",
+            "640x480",
+            "--directions",
+            "8",
+            "--accumulate",
+            "--adapter",
+            "fallback",
+            "--output",
+            "out",
+            "--frames",
+            "4",
+            "scene.svg",
+        ])
+        .unwrap();
+        assert_eq!(args.scene, PathBuf::from("scene.svg"));
+        assert_eq!(args.output_size(), vector![640, 480]);
+        assert_eq!(args.radiance_settings.light_directions, 8);
+        assert!(args.radiance_settings.temporal_accumulate);
+        assert!(args.device_settings.force_fallback_adapter);
+        assert_eq!(args.output, Some(PathBuf::from("out")));
+        assert_eq!(args.frames, 4);
+    }
+
+    #[test]
+    fn bake() {
+        let args = parse(&["bake", "--output", "out", "--min-frames", "2"]).unwrap();
+        let settings = args.bake.unwrap();
+        assert_eq!(settings.min_frames, 2);
+        assert_eq!(settings.max_frames, BakeSettings::default().max_frames);
+        assert!(args.radiance_settings.temporal_accumulate);
+        assert!(args.radiance_settings.direction_jitter);
+        assert_eq!(args.radiance_settings.temporal_blend, 0.0);
+    }
+
+    #[test]
+    fn unknown_options() {
+        assert_eq!(
+            parse(&["--fast"]).unwrap_err(),
+            CliError::UnknownOption("--fast".to_string())
+        );
+        assert_eq!(
+            parse(&["a.ron", "b.ron"]).unwrap_err(),
+            CliError::UnexpectedArgument("b.ron".to_string())
+        );
+    }
+
+    #[test]
+    fn missing_values() {
+        assert_eq!(
+            parse(&["--size"]).unwrap_err(),
+            CliError::MissingValue("--size")
+        );
+        assert_eq!(
+            parse(&["--output", "out", "--frames"]).unwrap_err(),
+            CliError::MissingValue("--frames")
+        );
+    }
+
+    #[test]
+    fn invalid_values() {
+        for args in [
+            &["--size", "640"][..],
+            &["--size", "0x480"],
+            &["--directions", "0"],
+            &["--bounces", "0"],
+            &["--bounces", "-1"],
+            &["--render-scale", "2"],
+            &["--tone-mapping", "filmic"],
+            &["--curve-tolerance", "0"],
+            &["--tolerance", "0"],
+            &["--tolerance", "-1e-3"],
+            &["--tolerance", "inf"],
+        ] {
+            assert!(
+                matches!(parse(args), Err(CliError::InvalidValue { option, .. }) if option == args[0]),
+                "{:?}",
+                args
+            );
+        }
+    }
+
+    #[test]
+    fn bake_only_options() {
+        assert_eq!(
+            parse(&["--output", "out", "--tolerance", "1e-3"]).unwrap_err(),
+            CliError::OnlyForBake("--tolerance")
+        );
+        assert_eq!(
+            parse(&["--max-frames", "8", "--min-frames", "2"]).unwrap_err(),
+            CliError::OnlyForBake("--max-frames")
+        );
+    }
+
+    #[test]
+    fn conflicts() {
+        for args in [
+            &["bake"][..],
+            &["bake", "--output", "out", "--frames", "2"],
+            &["bake", "--output", "out", "--max-frames", "0"],
+            &[
+                "bake",
+                "--output",
+                "out",
+                "--min-frames",
+                "9",
+                "--max-frames",
+                "8",
+            ],
+            &["bake", "--output", "out", "--max-frames", "8"],
+            &["bake", "--output", "out", "--compare-reference"],
+            &["--frames", "2"],
+            &["--output", "out", "--frames", "0"],
+            &["--output", "out", "--present-mode", "mailbox"],
+            &["--output", "out", "--save-scene", "scene.ron"],
+            &["--compare-reference"],
+        ] {
+            assert!(
+                matches!(parse(args), Err(CliError::Conflict(_))),
+                "{:?}",
+                args
+            );
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use palette::LinSrgb;
+use std::f32::consts::TAU;
+
+/// Light entering the scene from outside the window, by the world-space angle it comes from.
+/// Angles are counterclockwise from the positive x axis, in radians.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Environment {
+    /// The same radiance from every direction.
+    Ambient(LinSrgb),
+    /// A disc of `radiance` centered on `direction`, `angular_size` radians across.
+    Sun {
+        direction: f32,
+        angular_size: f32,
+        radiance: LinSrgb,
+    },
+    /// Radiance sampled at evenly spaced angles, starting at 0. Each sample covers the
+    /// `TAU / len` radians after its angle.
+    Map(Vec<LinSrgb>),
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Environment::Ambient(LinSrgb::new(0.0, 0.0, 0.0))
+    }
+}
+
+/// Length of the overlap of the circular arcs starting at `a` and `b`.
+fn arc_overlap(a: f32, a_width: f32, b: f32, b_width: f32) -> f32 {
+    let a = a.rem_euclid(TAU);
+    let b = b.rem_euclid(TAU);
+    [-TAU, 0.0, TAU]
+        .iter()
+        .map(|shift| ((a + a_width).min(b + shift + b_width) - a.max(b + shift)).max(0.0))
+        .sum()
+}
+
+impl Environment {
+    /// Average radiance over the arc of `width` radians centered on `angle`.
+    pub fn average_radiance(&self, angle: f32, width: f32) -> LinSrgb {
+        let start = angle - width / 2.0;
+        match self {
+            Environment::Ambient(radiance) => *radiance,
+            Environment::Sun {
+                direction,
+                angular_size,
+                radiance,
+            } => {
+                let overlap =
+                    arc_overlap(start, width, direction - angular_size / 2.0, *angular_size);
+                *radiance * (overlap / width)
+            }
+            Environment::Map(samples) => {
+                let sample_width = TAU / samples.len() as f32;
+                samples
+                    .iter()
+                    .enumerate()
+                    .fold(LinSrgb::new(0.0, 0.0, 0.0), |sum, (i, &sample)| {
+                        let overlap =
+                            arc_overlap(start, width, i as f32 * sample_width, sample_width);
+                        sum + sample * (overlap / width)
+                    })
+            }
+        }
+    }
+}
@@ -1,116 +1,474 @@
 #![feature(int_roundings)]
 
-use nalgebra::vector;
-use palette::{Srgb, Srgba};
-use state::radiance::RadianceSettings;
+use camera::Camera;
+use cli::Args;
+use nalgebra::{vector, Vector2};
+use scene::Scene;
+use state::exposure::AutoExposureSettings;
+use state::render::{ToneMapping, Upsampling, ViewMode};
 use state::State;
-use std::time::{Duration, Instant};
-use vertex::{Vertex, VertexList};
-use winit::dpi::PhysicalSize;
-use winit::event::Event;
-use winit::event_loop::EventLoop;
+use std::path::Path;
+use std::process::exit;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use watcher::SceneWatcher;
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::event::{
+    ElementState, Event, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent,
+};
+use winit::event_loop::{ControlFlow, EventLoop};
 
+mod bake;
+mod bitmap;
+mod camera;
+mod cli;
+mod environment;
+mod object;
+mod readback;
+mod reference;
+mod scene;
+mod screenshot;
 mod state;
+mod svg;
 mod texture;
 mod uniform;
 mod vertex;
+mod watcher;
 
-fn vertices() -> VertexList {
-    let black_alpha = Srgba::new(0.0, 0.0, 0.0, 0.01).into();
-    let black = Srgb::new(0.0, 0.0, 0.0);
-    let dim = Srgb::new(0.1, 0.1, 0.1);
-    let bright = Srgb::new(1.0, 1.0, 1.0);
-
-    let mut vertices = VertexList::new();
-    vertices
-        .triangle([
-            Vertex {
-                position: vector![0.0, 0.0],
-                normal: vector![0.0, 0.0],
-                albedo: Srgba::new(1.0, 1.0, 1.0, 0.01).into(),
-                radiance: black_alpha,
-            },
-            Vertex {
-                position: vector![0.0, 4000.0],
-                normal: vector![0.0, 0.0],
-                albedo: Srgba::new(1.0, 1.0, 1.0, 0.01).into(),
-                radiance: black_alpha,
-            },
-            Vertex {
-                position: vector![4000.0, 0.0],
-                normal: vector![0.0, 0.0],
-                albedo: Srgba::new(1.0, 1.0, 1.0, 0.01).into(),
-                radiance: black_alpha,
-            },
-        ])
-        .rectangle(
-            vector![300.0, 400.0],
-            vector![20.0, 140.0],
-            Srgb::new(0.5, 0.5, 0.5),
-            black,
-        )
-        .rectangle(
-            vector![300.0, 200.0],
-            vector![200.0, 20.0],
-            Srgb::new(0.9, 0.1, 0.1),
-            black,
-        )
-        .rectangle(
-            vector![0.0, 0.0],
-            vector![2000.0, 10.0],
-            Srgb::new(0.0, 0.0, 0.0),
-            dim,
-        )
-        .rectangle(
-            vector![0.0, 0.0],
-            vector![10.0, 2000.0],
-            Srgb::new(0.0, 0.0, 0.0),
-            bright,
-        )
-        .rectangle(
-            vector![800.0, 600.0],
-            vector![2000.0, 10.0],
-            Srgb::new(0.0, 0.0, 0.0),
-            dim,
-        )
-        .rectangle(
-            vector![800.0, 600.0],
-            vector![10.0, 2000.0],
-            Srgb::new(0.0, 0.0, 0.0),
-            dim,
+/// Steps the radiance settings with the arrow keys: up and down double and halve the light
+/// directions, right and left add and remove a bounce.
+fn adjust_radiance_settings(state: &mut State, key: VirtualKeyCode) {
+    let mut settings = state.radiance_settings();
+    match key {
+        VirtualKeyCode::Up => settings.light_directions *= 2,
+        VirtualKeyCode::Down => settings.light_directions = (settings.light_directions / 2).max(1),
+        VirtualKeyCode::Right => settings.light_bounces += 1,
+        VirtualKeyCode::Left => settings.light_bounces = (settings.light_bounces - 1).max(1),
+        _ => return,
+    }
+    match state.set_radiance_settings(settings) {
+        Ok(()) => println!(
+            "Light directions: {}, light bounces: {}",
+            settings.light_directions, settings.light_bounces
+        ),
+        Err(err) => eprintln!("Invalid radiance settings: {}", err),
+    }
+}
+
+/// `T` cycles through the tone mapping curves, `+` and `-` change the exposure by half a stop
+/// and `E` toggles auto exposure. `R` steps the render scale down to a quarter and `U` cycles
+/// through the upsampling filters.
+fn adjust_render_settings(state: &mut State, key: VirtualKeyCode) {
+    let mut settings = state.render_settings();
+    match key {
+        VirtualKeyCode::T => {
+            settings.tone_mapping = match settings.tone_mapping {
+                ToneMapping::Linear => ToneMapping::Reinhard,
+                ToneMapping::Reinhard => ToneMapping::Aces,
+                ToneMapping::Aces => ToneMapping::Agx,
+                ToneMapping::Agx => ToneMapping::Linear,
+            }
+        }
+        VirtualKeyCode::Equals | VirtualKeyCode::Plus | VirtualKeyCode::NumpadAdd => {
+            settings.exposure += 0.5
+        }
+        VirtualKeyCode::Minus | VirtualKeyCode::NumpadSubtract => settings.exposure -= 0.5,
+        VirtualKeyCode::E => {
+            settings.auto_exposure = match settings.auto_exposure {
+                Some(_) => None,
+                None => Some(AutoExposureSettings::default()),
+            }
+        }
+        VirtualKeyCode::R => {
+            settings.render_scale = if settings.render_scale > 0.25 {
+                settings.render_scale / 2.0
+            } else {
+                1.0
+            }
+        }
+        VirtualKeyCode::U => {
+            settings.upsampling = match settings.upsampling {
+                Upsampling::Nearest => Upsampling::Bilinear,
+                Upsampling::Bilinear => Upsampling::EdgeAware,
+                Upsampling::EdgeAware => Upsampling::Nearest,
+            }
+        }
+        _ => return,
+    }
+    state.set_render_settings(settings);
+    println!(
+        "Tone mapping: {:?}, exposure: {:+} EV, auto exposure: {}, render scale: {}, \
+         upsampling: {:?}",
+        settings.tone_mapping,
+        settings.exposure,
+        settings.auto_exposure.is_some(),
+        settings.render_scale,
+        settings.upsampling
+    );
+}
+
+/// The number keys pick what is shown: 1 the final image, 2 albedo, 3 emitted light, 4 normals,
+/// 5 alpha, 6 one light direction and 7 a luminance heatmap. `[` and `]` step the direction.
+fn select_view_mode(state: &mut State, key: VirtualKeyCode) {
+    let mut settings = state.render_settings();
+    let layer = match settings.view_mode {
+        ViewMode::DirectionalRadiance(layer) => layer,
+        _ => 0,
+    };
+    let light_directions = state.radiance_settings().light_directions;
+    settings.view_mode = match key {
+        VirtualKeyCode::Key1 => ViewMode::Final,
+        VirtualKeyCode::Key2 => ViewMode::Albedo,
+        VirtualKeyCode::Key3 => ViewMode::Emitted,
+        VirtualKeyCode::Key4 => ViewMode::Normal,
+        VirtualKeyCode::Key5 => ViewMode::Alpha,
+        VirtualKeyCode::Key6 => ViewMode::DirectionalRadiance(layer),
+        VirtualKeyCode::Key7 => ViewMode::Heatmap,
+        VirtualKeyCode::LBracket => {
+            ViewMode::DirectionalRadiance((layer + light_directions - 1) % light_directions)
+        }
+        VirtualKeyCode::RBracket => ViewMode::DirectionalRadiance((layer + 1) % light_directions),
+        _ => return,
+    };
+    state.set_render_settings(settings);
+    println!("View mode: {:?}", settings.view_mode);
+}
+
+/// F12 saves the radiance and the displayed frame to the working directory.
+fn take_screenshot(state: &State, key: VirtualKeyCode) {
+    if key != VirtualKeyCode::F12 {
+        return;
+    }
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let path = format!("screenshot-{}", timestamp);
+    match state.save_screenshot(&path) {
+        Ok(()) => println!("Saved {0}.pfm and {0}.png", path),
+        Err(err) => eprintln!("Failed to save screenshot: {}", err),
+    }
+}
+
+/// Renders `args.frames` frames without a window and saves the last one to `output`, or bakes
+/// the radiance if `args.bake` is set.
+async fn render_headless(args: &Args, scene: &Scene, output: &Path) {
+    let mut state = match State::init_headless(
+        args.output_size(),
+        scene.vertices(),
+        args.radiance_settings,
+        args.device_settings,
+    )
+    .await
+    {
+        Ok(state) => state,
+        Err(err) => {
+            eprintln!("Failed to initialize: {}", err);
+            exit(1);
+        }
+    };
+    if let Err(err) = state.set_bitmap(scene.texels()) {
+        eprintln!("Failed to load scene: {}", err);
+        exit(1);
+    }
+    state.set_objects(scene.objects());
+    state.set_environment(scene.environment.environment());
+    state.set_render_settings(args.render_settings);
+
+    if let Some(bake_settings) = &args.bake {
+        let (radiance, report) = match bake::bake(&mut state, bake_settings) {
+            Ok(result) => result,
+            Err(err) => {
+                eprintln!("Failed to bake: {}", err);
+                exit(1);
+            }
+        };
+        if report.converged {
+            println!("Converged after {} frames", report.frames);
+        } else {
+            println!(
+                "Stopped after {} frames, difference {:.3e} is above the tolerance",
+                report.frames, report.difference
+            );
+        }
+        let path = output.with_extension("pfm");
+        match screenshot::write_pfm(&path, &radiance) {
+            Ok(()) => println!("Saved {}", path.display()),
+            Err(err) => {
+                eprintln!("Failed to save output: {}", err);
+                exit(1);
+            }
+        }
+        return;
+    }
+
+    for _ in 0..args.frames {
+        if let Err(err) = state.render() {
+            eprintln!("Failed to render: {}", err);
+            exit(1);
+        }
+    }
+    if args.compare_reference {
+        let difference = bake::relative_difference(
+            &state.read_total_radiance(),
+            &state.compute_reference_radiance(),
         );
-    vertices
+        println!("Difference to the CPU reference: {:.3e}", difference);
+    }
+    match state.save_screenshot(output) {
+        Ok(()) => println!(
+            "Saved {} and {}",
+            output.with_extension("pfm").display(),
+            output.with_extension("png").display()
+        ),
+        Err(err) => {
+            eprintln!("Failed to save output: {}", err);
+            exit(1);
+        }
+    }
+}
+
+/// Moves the animated objects of `scene` to where they are `time` seconds in.
+fn animate(scene: &Scene, state: &mut State, time: f32) {
+    for object in &scene.objects {
+        if object.animations.is_empty() {
+            continue;
+        }
+        state.set_object_properties(&object.name, object.properties_at(time));
+    }
+}
+
+/// Mouse state for moving the camera: dragging with the left button pans, with the right button
+/// rotates around the center of the view, and scrolling zooms around the cursor.
+#[derive(Debug, Default)]
+struct CameraControls {
+    /// In view coordinates.
+    cursor: Option<Vector2<f32>>,
+    panning: bool,
+    rotating: bool,
+}
+
+/// Converts a cursor position, in physical pixels from the top left, to view coordinates.
+fn cursor_to_view(state: &State, position: PhysicalPosition<f64>) -> Vector2<f32> {
+    let surface_size = state.surface_size().cast::<f32>();
+    let position = vector![position.x as f32, surface_size.y - position.y as f32];
+    position * (state.view_size().x / surface_size.x)
+}
+
+fn control_camera(state: &mut State, controls: &mut CameraControls, event: &WindowEvent) {
+    let mut camera = state.camera();
+    let view_size = state.view_size();
+    match *event {
+        WindowEvent::CursorMoved { position, .. } => {
+            let cursor = cursor_to_view(state, position);
+            if let Some(last_cursor) = controls.cursor.replace(cursor) {
+                if controls.panning {
+                    camera.pan(cursor - last_cursor);
+                } else if controls.rotating {
+                    let center = view_size / 2.0;
+                    let angle =
+                        |point: Vector2<f32>| (point.y - center.y).atan2(point.x - center.x);
+                    // Turn the world with the cursor, so the camera turns the other way.
+                    camera.rotate(angle(last_cursor) - angle(cursor));
+                } else {
+                    return;
+                }
+            }
+        }
+        WindowEvent::CursorLeft { .. } => {
+            controls.cursor = None;
+            return;
+        }
+        WindowEvent::MouseInput { state, button, .. } => {
+            let pressed = state == ElementState::Pressed;
+            match button {
+                MouseButton::Left => controls.panning = pressed,
+                MouseButton::Right => controls.rotating = pressed,
+                _ => {}
+            }
+            return;
+        }
+        WindowEvent::MouseWheel { delta, .. } => {
+            let steps = match delta {
+                MouseScrollDelta::LineDelta(_, lines) => lines,
+                MouseScrollDelta::PixelDelta(pixels) => pixels.y as f32 / 40.0,
+            };
+            let anchor = controls.cursor.unwrap_or(view_size / 2.0);
+            camera.zoom_at(anchor, 1.1_f32.powf(steps), view_size);
+        }
+        _ => return,
+    }
+    state.set_camera(camera);
+}
+
+/// Home puts the camera back where it started.
+fn reset_camera(state: &mut State, key: VirtualKeyCode) {
+    if key == VirtualKeyCode::Home {
+        state.set_camera(Camera::new(state.view_size()));
+    }
 }
 
 async fn run() {
     env_logger::init();
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, cli::USAGE);
+            exit(2);
+        }
+    };
+    if args.help {
+        print!("{}", cli::USAGE);
+        return;
+    }
+
+    let mut scene = match Scene::load(&args.scene, args.curve_tolerance) {
+        Ok(scene) => scene,
+        Err(err) => {
+            eprintln!("Failed to load scene: {}", err);
+            exit(1);
+        }
+    };
+
+    if let Some(path) = &args.save_scene {
+        match scene.save(path) {
+            Ok(()) => println!("Saved {}", path.display()),
+            Err(err) => {
+                eprintln!("Failed to save scene: {}", err);
+                exit(1);
+            }
+        }
+        return;
+    }
+
+    if let Some(output) = &args.output {
+        render_headless(&args, &scene, output).await;
+        return;
+    }
+
     let event_loop = EventLoop::new();
-    let window = winit::window::WindowBuilder::new()
-        .with_min_inner_size(PhysicalSize::new(800, 600))
-        .build(&event_loop)
-        .unwrap();
-
-    let mut state = State::init(&window, vertices(), RadianceSettings {
-        light_directions: 32,
-        light_bounces: 3,
-        temporal_accumulate: false,
-    })
-    .await;
+    // An explicit `--size` replaces the minimum, so it can be smaller.
+    let window_builder = winit::window::WindowBuilder::new();
+    let window_builder = match args.size {
+        Some(size) => window_builder.with_inner_size(PhysicalSize::new(size.x, size.y)),
+        None => window_builder.with_min_inner_size(PhysicalSize::new(800, 600)),
+    };
+    let window = match window_builder.build(&event_loop) {
+        Ok(window) => window,
+        Err(err) => {
+            eprintln!("Failed to create window: {}", err);
+            exit(1);
+        }
+    };
+
+    let mut state = match State::init(
+        &window,
+        scene.vertices(),
+        args.radiance_settings,
+        args.device_settings,
+    )
+    .await
+    {
+        Ok(state) => state,
+        Err(err) => {
+            eprintln!("Failed to initialize: {}", err);
+            exit(1);
+        }
+    };
+    if let Err(err) = state.set_bitmap(scene.texels()) {
+        eprintln!("Failed to load scene: {}", err);
+        exit(1);
+    }
+    state.set_objects(scene.objects());
+    state.set_environment(scene.environment.environment());
+    state.set_render_settings(args.render_settings);
 
+    let mut scene_watcher = SceneWatcher::new(&args.scene, &scene, args.curve_tolerance);
     let mut frame_time = Duration::from_millis(100);
+    let mut camera_controls = CameraControls::default();
+    let mut animation_start = Instant::now();
 
-    event_loop.run(move |event, _, _| match event {
+    event_loop.run(move |event, _, control_flow| match event {
         Event::RedrawRequested(..) => {
             let before = Instant::now();
-            state.render();
+            animate(&scene, &mut state, (before - animation_start).as_secs_f32());
+            if let Err(err) = state.render() {
+                eprintln!("Failed to render: {}", err);
+                *control_flow = ControlFlow::Exit;
+                return;
+            }
             let after = Instant::now();
             let delta = after - before;
             frame_time = frame_time.mul_f32(0.9) + delta.mul_f32(0.1);
             let fps = 1.0 / frame_time.as_secs_f32();
             println!("Fps: {:?}", fps);
         }
+        Event::WindowEvent {
+            event: WindowEvent::Resized(size),
+            ..
+        } => {
+            state.resize(size, window.scale_factor());
+        }
+        Event::WindowEvent {
+            event:
+                WindowEvent::ScaleFactorChanged {
+                    scale_factor,
+                    new_inner_size,
+                },
+            ..
+        } => {
+            state.resize(*new_inner_size, scale_factor);
+        }
+        Event::WindowEvent {
+            event:
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(key),
+                            ..
+                        },
+                    ..
+                },
+            ..
+        } => {
+            adjust_radiance_settings(&mut state, key);
+            adjust_render_settings(&mut state, key);
+            select_view_mode(&mut state, key);
+            take_screenshot(&state, key);
+            reset_camera(&mut state, key);
+        }
+        Event::WindowEvent {
+            event:
+                ref event @ (WindowEvent::CursorMoved { .. }
+                | WindowEvent::CursorLeft { .. }
+                | WindowEvent::MouseInput { .. }
+                | WindowEvent::MouseWheel { .. }),
+            ..
+        } => control_camera(&mut state, &mut camera_controls, event),
         Event::MainEventsCleared => {
-            window.request_redraw();
+            match scene_watcher.poll() {
+                // The bitmap goes first, as it is the only part the device can refuse.
+                Some(Ok(new_scene)) => match state.set_bitmap(new_scene.texels()) {
+                    Ok(()) => {
+                        scene = new_scene;
+                        state.set_vertices(scene.vertices());
+                        state.set_objects(scene.objects());
+                        state.set_environment(scene.environment.environment());
+                        animation_start = Instant::now();
+                    }
+                    Err(err) => eprintln!("Failed to reload scene: {}", err),
+                },
+                // Keep rendering the last scene that loaded.
+                Some(Err(err)) => eprintln!("Failed to reload scene: {}", err),
+                None => {}
+            }
+            // Sleep until the window is restored instead of spinning on skipped frames.
+            if state.minimized() {
+                *control_flow = ControlFlow::Wait;
+            } else {
+                *control_flow = ControlFlow::Poll;
+                window.request_redraw();
+            }
         }
         _ => {}
     });
@@ -0,0 +1,71 @@
+use bytemuck::{Pod, Zeroable};
+use nalgebra::{Affine2, Matrix2, Vector2};
+use palette::{LinSrgb, LinSrgba};
+
+use crate::vertex::VertexList;
+
+/// Placement and color of an object, which can change every frame without uploading its mesh
+/// again.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ObjectProperties {
+    /// From the mesh to world coordinates.
+    pub transform: Affine2<f32>,
+    /// Multiplies the albedo and alpha of the mesh.
+    pub albedo: LinSrgba,
+    /// Multiplies the radiance the mesh emits.
+    pub emission: LinSrgb,
+}
+
+impl Default for ObjectProperties {
+    fn default() -> Self {
+        Self {
+            transform: Affine2::identity(),
+            albedo: LinSrgba::new(1.0, 1.0, 1.0, 1.0),
+            emission: LinSrgb::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+/// A named mesh drawn on top of the static scene geometry.
+#[derive(Debug, Clone)]
+pub struct Object {
+    pub name: String,
+    pub mesh: VertexList,
+    pub properties: ObjectProperties,
+}
+
+/// Must match `ObjectUniforms` in `prerender.vert`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+pub struct ObjectUniforms {
+    transform_x: Vector2<f32>,
+    transform_y: Vector2<f32>,
+    translation: Vector2<f32>,
+    /// Normals go through the inverse transpose, so they stay perpendicular under scaling.
+    normal_x: Vector2<f32>,
+    normal_y: Vector2<f32>,
+    _padding: [f32; 2],
+    albedo: LinSrgba,
+    emission: LinSrgba,
+}
+
+impl From<&ObjectProperties> for ObjectUniforms {
+    fn from(properties: &ObjectProperties) -> Self {
+        let matrix = properties.transform.matrix();
+        let linear: Matrix2<f32> = matrix.fixed_slice::<2, 2>(0, 0).into();
+        // A degenerate transform flattens the object, so any normal will do.
+        let normal = linear
+            .try_inverse()
+            .map_or_else(Matrix2::identity, |inverse| inverse.transpose());
+        Self {
+            transform_x: linear.column(0).into(),
+            transform_y: linear.column(1).into(),
+            translation: matrix.fixed_slice::<2, 1>(0, 2).into(),
+            normal_x: normal.column(0).into(),
+            normal_y: normal.column(1).into(),
+            _padding: [0.0; 2],
+            albedo: properties.albedo,
+            emission: LinSrgba::from(properties.emission),
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use bytemuck::{cast_slice, Pod};
+use nalgebra::Vector2;
+use std::mem::size_of;
+use std::num::NonZeroU32;
+use wgpu::*;
+
+#[derive(Debug, Clone)]
+pub struct ImageBuffer<T> {
+    pub size: Vector2<u32>,
+    pub pixels: Vec<T>, // Row-major, starting at the top left.
+}
+
+/// Copies a texture into host memory, where `T` matches the texel format.
+pub fn read_texture<T: Pod>(
+    device: &Device,
+    queue: &Queue,
+    texture: &Texture,
+    size: Vector2<u32>,
+) -> ImageBuffer<T> {
+    let unpadded_bytes_per_row = size.x * size_of::<T>() as u32;
+    // Buffer copies need rows aligned to 256 bytes, so strip the padding afterwards.
+    let padded_bytes_per_row =
+        unpadded_bytes_per_row.next_multiple_of(COPY_BYTES_PER_ROW_ALIGNMENT);
+
+    let buffer = device.create_buffer(&BufferDescriptor {
+        label: None,
+        size: (padded_bytes_per_row * size.y) as BufferAddress,
+        usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor { label: None });
+    encoder.copy_texture_to_buffer(
+        ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: Origin3d::ZERO,
+            aspect: TextureAspect::All,
+        },
+        ImageCopyBuffer {
+            buffer: &buffer,
+            layout: ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(padded_bytes_per_row),
+                rows_per_image: None,
+            },
+        },
+        Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+    );
+    queue.submit(std::iter::once(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    let mapping = slice.map_async(MapMode::Read);
+    device.poll(Maintain::Wait);
+    pollster::block_on(mapping).expect("Failed to map readback buffer");
+
+    let data = slice.get_mapped_range();
+    let mut pixels = Vec::with_capacity((size.x * size.y) as usize);
+    for row in data.chunks_exact(padded_bytes_per_row as usize) {
+        pixels.extend_from_slice(cast_slice(&row[..unpadded_bytes_per_row as usize]));
+    }
+    drop(data);
+    buffer.unmap();
+
+    ImageBuffer { size, pixels }
+}
@@ -0,0 +1,419 @@
+//! CPU port of `radiance.comp` and `radiance_collect.frag`, for checking the GPU output.
+//! Mirrors the shaders step by step, including the rgb9e5 quantization of the directional
+//! radiance, so results should match up to float rounding.
+//!
+//! Only the radiance is solved here. Its inputs are the prerender textures, which the GPU
+//! rasterizes from the scene, so there is no CPU path from a scene to its radiance.
+
+use nalgebra::{vector, Vector2};
+use palette::{LinSrgb, LinSrgba};
+use std::f32::consts::TAU;
+
+use crate::readback::ImageBuffer;
+use crate::state::radiance::{
+    RadianceDirectionFlags, RadianceDirectionalUniforms, RadianceSettings, Workgroup,
+    RADIANCE_WORKGROUP_SIZE,
+};
+
+const RGB9E5_MANTISSA_BITS: i32 = 9;
+const RGB9E5_MANTISSA_MASK: u32 = 0x1FF;
+const RGB9E5_EXPONENT_BITS: i32 = 5;
+const RGB9E5_EXP_BIAS: i32 = 15;
+const RGB9E5_EXP_MAX: i32 = (1 << RGB9E5_EXPONENT_BITS) - RGB9E5_EXP_BIAS - 1;
+const MAX_RGB9E5_MANTISSA_VALUES: i32 = 1 << RGB9E5_MANTISSA_BITS;
+const MAX_RGB9E5: f32 = (MAX_RGB9E5_MANTISSA_VALUES - 1) as f32 / MAX_RGB9E5_MANTISSA_VALUES as f32
+    * (1 << RGB9E5_EXP_MAX) as f32;
+
+pub fn to_rgb9e5(v: LinSrgb) -> u32 {
+    let clamped = [v.red, v.green, v.blue].map(|x| x.clamp(0.0, MAX_RGB9E5));
+
+    let max_val = clamped[0].max(clamped[1]).max(clamped[2]);
+
+    let exponent = (((max_val.to_bits() >> 23) & 0xFF) as i32 - 126)
+        .clamp(-RGB9E5_EXP_BIAS, RGB9E5_EXP_MAX + 1);
+
+    let written_exponent = (exponent + RGB9E5_EXP_BIAS) as u32;
+
+    let scale = ((RGB9E5_MANTISSA_BITS - exponent) as f32).exp2();
+
+    let mantissas = clamped.map(|x| (x * scale + 0.5) as u32);
+
+    (mantissas[0] & RGB9E5_MANTISSA_MASK)
+        | ((mantissas[1] & RGB9E5_MANTISSA_MASK) << 9)
+        | ((mantissas[2] & RGB9E5_MANTISSA_MASK) << 18)
+        | (written_exponent << 27)
+}
+
+pub fn from_rgb9e5(encoded: u32) -> LinSrgb {
+    let exponent = (encoded >> 27) as i32 - RGB9E5_EXP_BIAS;
+    let scale = ((exponent - RGB9E5_MANTISSA_BITS) as f32).exp2();
+
+    LinSrgb::new(
+        (encoded & RGB9E5_MANTISSA_MASK) as f32 * scale,
+        ((encoded >> 9) & RGB9E5_MANTISSA_MASK) as f32 * scale,
+        ((encoded >> 18) & RGB9E5_MANTISSA_MASK) as f32 * scale,
+    )
+}
+
+/// The contents of `PrerenderTextures`.
+#[derive(Debug, Clone)]
+pub struct PrerenderImages {
+    pub albedo_lin: ImageBuffer<LinSrgba>,
+    pub radiance_lin: ImageBuffer<LinSrgba>,
+    pub normal: ImageBuffer<Vector2<f32>>,
+}
+
+struct Solver<'a> {
+    size: Vector2<i32>,
+    inv_light_directions: f32,
+    normal: &'a [Vector2<f32>],
+    total_radiance: Vec<LinSrgba>,
+    directional_radiance: Vec<u32>,
+}
+
+impl Solver<'_> {
+    fn index(&self, position: Vector2<i32>) -> Option<usize> {
+        // Out of bounds fetches read zero and stores are dropped, as with robust buffer access.
+        if position.x < 0
+            || position.y < 0
+            || position.x >= self.size.x
+            || position.y >= self.size.y
+        {
+            None
+        } else {
+            Some((position.y * self.size.x + position.x) as usize)
+        }
+    }
+
+    fn step(
+        &mut self,
+        position: Vector2<i32>,
+        direction: Vector2<f32>,
+        light_direction_index: usize,
+        radiance: &mut LinSrgb,
+    ) {
+        let index = self.index(position);
+        let normal = index.map_or(Vector2::zeros(), |i| self.normal[i]);
+        let current_radiance = index.map_or(LinSrgba::new(0.0, 0.0, 0.0, 0.0), |i| {
+            self.total_radiance[i]
+        });
+        let alpha = current_radiance.alpha;
+        let mut light_in_direction = self.inv_light_directions;
+        if let Some(i) = index {
+            let layer = (self.size.x * self.size.y) as usize * light_direction_index;
+            self.directional_radiance[layer + i] = to_rgb9e5(*radiance * alpha);
+        }
+        if normal != Vector2::zeros() {
+            light_in_direction *= normal.dot(&direction) * TAU;
+        }
+        *radiance =
+            *radiance * (1.0 - alpha) + current_radiance.color * light_in_direction.max(0.0);
+    }
+
+    fn trace(
+        &mut self,
+        total_offset: i32,
+        light_direction_index: usize,
+        uf: &RadianceDirectionalUniforms,
+    ) {
+        let slope = uf.slope;
+        let mut radiance = uf.starting_radiance;
+        let vertical = uf.flags.contains(RadianceDirectionFlags::VERTICAL_TRACING);
+        let reverse = uf.flags.contains(RadianceDirectionFlags::REVERSE_DIRECTION);
+
+        // Tracing along y is tracing along x with the axes swapped.
+        let size = if vertical { self.size.yx() } else { self.size };
+        let position = |i: i32| {
+            let p = vector![i, total_offset + (i as f32 * slope).floor() as i32];
+            if vertical {
+                p.yx()
+            } else {
+                p
+            }
+        };
+        let direction = if vertical {
+            vector![slope, 1.0].normalize()
+        } else {
+            vector![1.0, slope].normalize()
+        };
+
+        let intersection_a = -(total_offset as f32) / slope;
+        let intersection_b = (size.y as f32 - total_offset as f32) / slope;
+        let start = 0.max(intersection_a.min(intersection_b) as i32);
+        if !reverse {
+            let stop = size.x.min(intersection_a.max(intersection_b).ceil() as i32);
+            for i in start..stop {
+                self.step(position(i), direction, light_direction_index, &mut radiance);
+            }
+        } else {
+            let stop = (size.x - 1).min(intersection_a.max(intersection_b).ceil() as i32);
+            for i in (start..=stop).rev() {
+                self.step(
+                    position(i),
+                    -direction,
+                    light_direction_index,
+                    &mut radiance,
+                );
+            }
+        }
+    }
+
+    fn collect(&mut self, albedo: &[LinSrgba], emitted: &[LinSrgba], light_directions: usize) {
+        let pixels = (self.size.x * self.size.y) as usize;
+        for i in 0..pixels {
+            let mut radiance = LinSrgb::new(0.0, 0.0, 0.0);
+            for direction in 0..light_directions {
+                radiance += from_rgb9e5(self.directional_radiance[direction * pixels + i]);
+            }
+            let albedo = albedo[i];
+            self.total_radiance[i] = LinSrgba {
+                color: radiance * albedo.color + emitted[i].color,
+                alpha: albedo.alpha,
+            };
+        }
+    }
+}
+
+/// Computes `total_radiance` for a single frame starting from `inputs.radiance_lin`, the way
+/// `RadianceState::render` does before any temporal accumulation.
+///
+/// `directional_uniforms` and `workgroups` come from `RadianceState::compute_radiance_uniforms`.
+pub fn solve(
+    inputs: &PrerenderImages,
+    settings: RadianceSettings,
+    directional_uniforms: &[RadianceDirectionalUniforms],
+    workgroups: &[Workgroup],
+) -> ImageBuffer<LinSrgba> {
+    let size = inputs.albedo_lin.size;
+    let light_directions = settings.light_directions as usize;
+    let mut solver = Solver {
+        size: size.cast(),
+        inv_light_directions: 1.0 / settings.light_directions as f32,
+        normal: &inputs.normal.pixels,
+        total_radiance: inputs.radiance_lin.pixels.clone(),
+        directional_radiance: vec![0; (size.x * size.y) as usize * light_directions],
+    };
+
+    for _ in 0..settings.light_bounces {
+        for workgroup in workgroups {
+            let index = workgroup.light_direction_index as usize;
+            for local in 0..RADIANCE_WORKGROUP_SIZE as i32 {
+                solver.trace(
+                    workgroup.offset + local,
+                    index,
+                    &directional_uniforms[index],
+                );
+            }
+        }
+        solver.collect(
+            &inputs.albedo_lin.pixels,
+            &inputs.radiance_lin.pixels,
+            light_directions,
+        );
+    }
+
+    ImageBuffer {
+        size,
+        pixels: solver.total_radiance,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::Environment;
+    use crate::scene::Scene;
+    use crate::state::radiance::RadianceState;
+    use crate::state::{DeviceSettings, State};
+    use crate::svg;
+
+    fn image<T: Clone>(size: Vector2<u32>, pixel: T) -> ImageBuffer<T> {
+        ImageBuffer {
+            size,
+            pixels: vec![pixel; (size.x * size.y) as usize],
+        }
+    }
+
+    /// Empty air, with pixels set through `add_pixel`.
+    fn empty_scene(size: Vector2<u32>) -> PrerenderImages {
+        let clear = LinSrgba::new(0.0, 0.0, 0.0, 0.0);
+        PrerenderImages {
+            albedo_lin: image(size, clear),
+            radiance_lin: image(size, clear),
+            normal: image(size, Vector2::zeros()),
+        }
+    }
+
+    /// Like the prerender pass, the radiance takes the alpha of the albedo.
+    fn add_pixel(inputs: &mut PrerenderImages, index: usize, albedo: LinSrgba, emission: LinSrgb) {
+        inputs.albedo_lin.pixels[index] = albedo;
+        inputs.radiance_lin.pixels[index] = LinSrgba {
+            color: emission,
+            alpha: albedo.alpha,
+        };
+    }
+
+    fn solve_scene(
+        inputs: &PrerenderImages,
+        settings: RadianceSettings,
+        environment: &Environment,
+    ) -> ImageBuffer<LinSrgba> {
+        let (directional_uniforms, workgroups) = RadianceState::compute_radiance_uniforms(
+            inputs.albedo_lin.size,
+            settings.light_directions,
+            0.0,
+            environment,
+            0.0,
+        );
+        solve(inputs, settings, &directional_uniforms, &workgroups)
+    }
+
+    fn settings(light_directions: u32, light_bounces: u32) -> RadianceSettings {
+        RadianceSettings {
+            light_directions,
+            light_bounces,
+            temporal_accumulate: false,
+            temporal_blend: 0.0,
+            direction_jitter: false,
+        }
+    }
+
+    fn assert_close(a: LinSrgb, b: LinSrgb, tolerance: f32) {
+        let difference = (a - b).into_components();
+        assert!(
+            [difference.0, difference.1, difference.2]
+                .iter()
+                .all(|x| x.abs() <= tolerance),
+            "{:?} is not within {} of {:?}",
+            a,
+            tolerance,
+            b
+        );
+    }
+
+    #[test]
+    fn rgb9e5_round_trip() {
+        for color in [
+            LinSrgb::new(0.0f32, 0.0, 0.0),
+            LinSrgb::new(1.0, 0.5, 0.25),
+            LinSrgb::new(0.001, 0.02, 0.3),
+            LinSrgb::new(3.0, 100.0, 0.0),
+            LinSrgb::new(60000.0, 1.0, 1e-6),
+        ] {
+            let max = color.red.max(color.green).max(color.blue);
+            // The 9 bit mantissas share the exponent of the largest channel.
+            let tolerance = max / (1 << RGB9E5_MANTISSA_BITS) as f32;
+            assert_close(from_rgb9e5(to_rgb9e5(color)), color, tolerance);
+        }
+    }
+
+    #[test]
+    fn rgb9e5_clamps() {
+        let decoded = from_rgb9e5(to_rgb9e5(LinSrgb::new(-1.0, 1e9, f32::INFINITY)));
+        assert_eq!(decoded, LinSrgb::new(0.0, MAX_RGB9E5, MAX_RGB9E5));
+    }
+
+    #[test]
+    fn solve_without_light_is_black() {
+        let size = vector![32, 32];
+        let mut inputs = empty_scene(size);
+        add_pixel(
+            &mut inputs,
+            16 * 32 + 16,
+            LinSrgba::new(1.0, 1.0, 1.0, 1.0),
+            LinSrgb::new(0.0, 0.0, 0.0),
+        );
+        let black = Environment::Ambient(LinSrgb::new(0.0, 0.0, 0.0));
+        let radiance = solve_scene(&inputs, settings(16, 2), &black);
+        assert!(radiance
+            .pixels
+            .iter()
+            .all(|pixel| pixel.color == LinSrgb::new(0.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn solve_reflects_ambient_light() {
+        let size = vector![32, 32];
+        let mut inputs = empty_scene(size);
+        let index = 16 * 32 + 16;
+        add_pixel(
+            &mut inputs,
+            index,
+            LinSrgba::new(0.5, 1.0, 0.25, 1.0),
+            LinSrgb::new(0.0, 0.0, 0.0),
+        );
+        let ambient = LinSrgb::new(1.0, 2.0, 4.0);
+        let radiance = solve_scene(&inputs, settings(16, 1), &Environment::Ambient(ambient));
+
+        // A lone surface sees the environment in every direction, which adds up to the ambient
+        // radiance times its albedo.
+        assert_close(
+            radiance.pixels[index].color,
+            LinSrgb::new(0.5, 2.0, 1.0),
+            0.02,
+        );
+        // Air doesn't reflect.
+        assert_eq!(radiance.pixels[0].color, LinSrgb::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn solve_lights_surfaces_around_an_emitter() {
+        let size = vector![32, 32];
+        let mut inputs = empty_scene(size);
+        let emitter = 16 * 32 + 8;
+        let surface = 16 * 32 + 24;
+        let emission = LinSrgb::new(8.0, 4.0, 2.0);
+        let black = LinSrgb::new(0.0, 0.0, 0.0);
+        add_pixel(
+            &mut inputs,
+            emitter,
+            LinSrgba::new(0.0, 0.0, 0.0, 1.0),
+            emission,
+        );
+        add_pixel(
+            &mut inputs,
+            surface,
+            LinSrgba::new(1.0, 1.0, 1.0, 1.0),
+            black,
+        );
+        let radiance = solve_scene(&inputs, settings(32, 1), &Environment::Ambient(black));
+
+        assert_eq!(radiance.pixels[emitter].color, emission);
+        let lit = radiance.pixels[surface].color;
+        assert!(lit.red > 0.0 && lit.red < emission.red, "{:?}", lit);
+        // The surface is white, so it keeps the color of the light.
+        assert_close(lit, emission * (lit.red / emission.red), 1e-3);
+    }
+
+    /// Compares the GPU radiance of the default scene with the reference on the fallback
+    /// adapter, which many machines and CI runners don't have. Run it with `--ignored` where
+    /// there is one.
+    #[test]
+    #[ignore = "needs a fallback graphics adapter"]
+    fn solve_matches_gpu() {
+        let scene = Scene::load("scenes/default.ron", svg::DEFAULT_TOLERANCE).unwrap();
+        let state = pollster::block_on(State::init_headless(
+            vector![256, 192],
+            scene.vertices(),
+            settings(16, 3),
+            DeviceSettings {
+                force_fallback_adapter: true,
+                ..DeviceSettings::default()
+            },
+        ));
+        let mut state = match state {
+            Ok(state) => state,
+            Err(err) => panic!("Failed to initialize: {}", err),
+        };
+        state.set_bitmap(scene.texels()).unwrap();
+        state.set_objects(scene.objects());
+        state.set_environment(scene.environment.environment());
+        state.render().unwrap();
+
+        let difference = crate::bake::relative_difference(
+            &state.read_total_radiance(),
+            &state.compute_reference_radiance(),
+        );
+        assert!(difference < 1e-3, "difference {:e}", difference);
+    }
+}
@@ -0,0 +1,1222 @@
+use nalgebra::{Affine2, Isometry2, Matrix3, Rotation2, Translation2, Vector2};
+use palette::{Alpha, Srgb, Srgba};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::f32::consts::TAU;
+use std::fmt::{self, Display, Formatter};
+use std::path::{Path, PathBuf};
+
+use crate::bitmap::{Bitmap, BitmapError};
+use crate::environment::Environment;
+use crate::object::{Object, ObjectProperties};
+use crate::svg::{self, SvgError};
+use crate::vertex::{is_simple_polygon, ShapeError, Vertex, VertexList};
+
+/// An sRGB color. Values above 1 are allowed for bright emitters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Color(pub f32, pub f32, pub f32);
+
+impl From<Color> for Srgb {
+    fn from(color: Color) -> Self {
+        Srgb::new(color.0, color.1, color.2)
+    }
+}
+
+fn opaque() -> f32 {
+    1.0
+}
+
+fn is_opaque(alpha: &f32) -> bool {
+    *alpha == 1.0
+}
+
+fn default_segments() -> u32 {
+    32
+}
+
+fn is_default_segments(segments: &u32) -> bool {
+    *segments == default_segments()
+}
+
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SceneVertex {
+    pub position: [f32; 2],
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub normal: [f32; 2],
+    pub albedo: Color,
+    #[serde(default = "opaque", skip_serializing_if = "is_opaque")]
+    pub alpha: f32,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub radiance: Color,
+}
+
+/// The shapes `VertexList` can build. The emitted radiance shares its alpha with the albedo.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Shape {
+    Triangle {
+        vertices: [SceneVertex; 3],
+    },
+    Parallelogram {
+        center: [f32; 2],
+        x: [f32; 2],
+        y: [f32; 2],
+        albedo: Color,
+        #[serde(default = "opaque", skip_serializing_if = "is_opaque")]
+        alpha: f32,
+        #[serde(default, skip_serializing_if = "is_default")]
+        radiance: Color,
+    },
+    Rectangle {
+        center: [f32; 2],
+        half_size: [f32; 2],
+        albedo: Color,
+        #[serde(default = "opaque", skip_serializing_if = "is_opaque")]
+        alpha: f32,
+        #[serde(default, skip_serializing_if = "is_default")]
+        radiance: Color,
+    },
+    Circle {
+        center: [f32; 2],
+        radius: f32,
+        #[serde(
+            default = "default_segments",
+            skip_serializing_if = "is_default_segments"
+        )]
+        segments: u32,
+        albedo: Color,
+        #[serde(default = "opaque", skip_serializing_if = "is_opaque")]
+        alpha: f32,
+        #[serde(default, skip_serializing_if = "is_default")]
+        radiance: Color,
+    },
+    /// An ellipse through `center + x` and `center + y`.
+    Ellipse {
+        center: [f32; 2],
+        x: [f32; 2],
+        y: [f32; 2],
+        #[serde(
+            default = "default_segments",
+            skip_serializing_if = "is_default_segments"
+        )]
+        segments: u32,
+        albedo: Color,
+        #[serde(default = "opaque", skip_serializing_if = "is_opaque")]
+        alpha: f32,
+        #[serde(default, skip_serializing_if = "is_default")]
+        radiance: Color,
+    },
+    /// A polygon, convex or not, whose edges don't cross or touch.
+    Polygon {
+        points: Vec<[f32; 2]>,
+        albedo: Color,
+        #[serde(default = "opaque", skip_serializing_if = "is_opaque")]
+        alpha: f32,
+        #[serde(default, skip_serializing_if = "is_default")]
+        radiance: Color,
+    },
+    /// A line through `points`, with round caps and joins.
+    Line {
+        points: Vec<[f32; 2]>,
+        thickness: f32,
+        albedo: Color,
+        #[serde(default = "opaque", skip_serializing_if = "is_opaque")]
+        alpha: f32,
+        #[serde(default, skip_serializing_if = "is_default")]
+        radiance: Color,
+    },
+    /// A line along a circle, counterclockwise from `start` to `end` degrees.
+    Arc {
+        center: [f32; 2],
+        radius: f32,
+        start: f32,
+        end: f32,
+        thickness: f32,
+        #[serde(
+            default = "default_segments",
+            skip_serializing_if = "is_default_segments"
+        )]
+        segments: u32,
+        albedo: Color,
+        #[serde(default = "opaque", skip_serializing_if = "is_opaque")]
+        alpha: f32,
+        #[serde(default, skip_serializing_if = "is_default")]
+        radiance: Color,
+    },
+}
+
+/// `Environment` with sRGB colors and angles in degrees.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SceneEnvironment {
+    Ambient(Color),
+    Sun {
+        direction: f32,
+        angular_size: f32,
+        radiance: Color,
+    },
+    Map(Vec<Color>),
+}
+
+fn unit_scale() -> [f32; 2] {
+    [1.0, 1.0]
+}
+
+fn is_unit_scale(scale: &[f32; 2]) -> bool {
+    *scale == unit_scale()
+}
+
+fn white() -> Color {
+    Color(1.0, 1.0, 1.0)
+}
+
+fn is_white(color: &Color) -> bool {
+    *color == white()
+}
+
+/// Scales, then rotates counterclockwise by `rotation` degrees, then translates.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SceneTransform {
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub translation: [f32; 2],
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub rotation: f32,
+    #[serde(default = "unit_scale", skip_serializing_if = "is_unit_scale")]
+    pub scale: [f32; 2],
+}
+
+impl Default for SceneTransform {
+    fn default() -> Self {
+        Self {
+            translation: [0.0; 2],
+            rotation: 0.0,
+            scale: unit_scale(),
+        }
+    }
+}
+
+/// Periodic motion of an object, following a sine wave over `period` seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SceneAnimation {
+    /// Rotates back and forth by up to `angle` degrees around `pivot`, in world coordinates.
+    Swing {
+        pivot: [f32; 2],
+        angle: f32,
+        period: f32,
+    },
+    /// Moves back and forth by up to `offset`.
+    Slide { offset: [f32; 2], period: f32 },
+    /// Scales the emission between `min` and `max`.
+    Pulse { min: f32, max: f32, period: f32 },
+}
+
+/// Shapes that move together, drawn over the static shapes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneObject {
+    pub name: String,
+    pub shapes: Vec<Shape>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub transform: SceneTransform,
+    /// Multiplies the albedo of the shapes.
+    #[serde(default = "white", skip_serializing_if = "is_white")]
+    pub albedo: Color,
+    /// Multiplies the radiance of the shapes.
+    #[serde(default = "white", skip_serializing_if = "is_white")]
+    pub emission: Color,
+    /// Applied on top of `transform` and `emission`, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub animations: Vec<SceneAnimation>,
+}
+
+impl Default for SceneEnvironment {
+    fn default() -> Self {
+        SceneEnvironment::Ambient(Color::default())
+    }
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+/// A tile of a tilemap, filled with one color.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SceneTile {
+    pub albedo: Color,
+    #[serde(default = "opaque", skip_serializing_if = "is_opaque")]
+    pub alpha: f32,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub radiance: Color,
+}
+
+/// Texels that go straight into the prerender textures instead of being rasterized. Its
+/// bottom left corner is at `origin`, in world coordinates.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SceneBitmap {
+    /// PNG images of the same size, relative to the scene file. Each texel is `texel_size`
+    /// world pixels wide.
+    Images {
+        origin: [f32; 2],
+        texel_size: f32,
+        albedo: PathBuf,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        emission: Option<PathBuf>,
+        /// Multiplies the emission image, which can't go above 1 by itself.
+        #[serde(default = "opaque", skip_serializing_if = "is_opaque")]
+        emission_intensity: f32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        normal: Option<PathBuf>,
+        #[serde(default, skip_serializing_if = "is_false")]
+        normals_from_alpha: bool,
+    },
+    /// Rows of characters from the top, each a tile of `tile_size` world pixels from
+    /// `palette`. Spaces are empty.
+    Tilemap {
+        origin: [f32; 2],
+        tile_size: f32,
+        palette: BTreeMap<char, SceneTile>,
+        rows: Vec<String>,
+        #[serde(default, skip_serializing_if = "is_false")]
+        normals_from_alpha: bool,
+    },
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Scene {
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub environment: SceneEnvironment,
+    pub shapes: Vec<Shape>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub objects: Vec<SceneObject>,
+    /// Drawn under the shapes and objects.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bitmap: Option<SceneBitmap>,
+    /// `bitmap` as loaded by `Scene::load`.
+    #[serde(skip)]
+    texels: Option<Bitmap>,
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        error: ron::Error,
+    },
+    Svg {
+        path: PathBuf,
+        error: SvgError,
+    },
+    Serialize {
+        path: PathBuf,
+        error: ron::Error,
+    },
+    Invalid {
+        path: PathBuf,
+        shape: usize,
+        reason: String,
+    },
+    InvalidEnvironment {
+        path: PathBuf,
+        reason: String,
+    },
+    InvalidObject {
+        path: PathBuf,
+        object: String,
+        reason: String,
+    },
+    InvalidBitmap {
+        path: PathBuf,
+        reason: String,
+    },
+    Bitmap {
+        path: PathBuf,
+        error: BitmapError,
+    },
+}
+
+impl Display for SceneError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            SceneError::Parse { path, error } => write!(f, "{}: {}", path.display(), error),
+            SceneError::Svg { path, error } => write!(f, "{}: {}", path.display(), error),
+            SceneError::Serialize { path, error } => {
+                write!(f, "{}: cannot serialize scene: {}", path.display(), error)
+            }
+            SceneError::Invalid {
+                path,
+                shape,
+                reason,
+            } => write!(f, "{}: shape {}: {}", path.display(), shape, reason),
+            SceneError::InvalidEnvironment { path, reason } => {
+                write!(f, "{}: environment: {}", path.display(), reason)
+            }
+            SceneError::InvalidObject {
+                path,
+                object,
+                reason,
+            } => write!(f, "{}: object {:?}: {}", path.display(), object, reason),
+            SceneError::InvalidBitmap { path, reason } => {
+                write!(f, "{}: bitmap: {}", path.display(), reason)
+            }
+            SceneError::Bitmap { path, error } => {
+                write!(f, "{}: bitmap: {}", path.display(), error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+fn check_color(name: &str, color: Color) -> Result<(), String> {
+    if [color.0, color.1, color.2]
+        .iter()
+        .all(|x| x.is_finite() && *x >= 0.0)
+    {
+        Ok(())
+    } else {
+        Err(format!("{} must be finite and non-negative", name))
+    }
+}
+
+fn check_alpha(alpha: f32) -> Result<(), String> {
+    if (0.0..=1.0).contains(&alpha) {
+        Ok(())
+    } else {
+        Err(format!("alpha must be between 0 and 1, got {}", alpha))
+    }
+}
+
+fn check_positive(name: &str, x: f32) -> Result<(), String> {
+    if x.is_finite() && x > 0.0 {
+        Ok(())
+    } else {
+        Err(format!("{} must be finite and positive", name))
+    }
+}
+
+fn check_segments(segments: u32, min: u32) -> Result<(), String> {
+    if segments >= min {
+        Ok(())
+    } else {
+        Err(format!(
+            "segments must be at least {}, got {}",
+            min, segments
+        ))
+    }
+}
+
+fn check_vector(name: &str, v: [f32; 2]) -> Result<(), String> {
+    if v.iter().all(|x| x.is_finite()) {
+        Ok(())
+    } else {
+        Err(format!("{} must be finite", name))
+    }
+}
+
+impl Shape {
+    fn validate(&self) -> Result<(), String> {
+        match *self {
+            Shape::Triangle { vertices } => vertices.iter().try_for_each(|vertex| {
+                check_vector("position", vertex.position)?;
+                check_vector("normal", vertex.normal)?;
+                check_color("albedo", vertex.albedo)?;
+                check_alpha(vertex.alpha)?;
+                check_color("radiance", vertex.radiance)
+            }),
+            Shape::Parallelogram {
+                center,
+                x,
+                y,
+                albedo,
+                alpha,
+                radiance,
+            } => {
+                check_vector("center", center)?;
+                check_vector("x", x)?;
+                check_vector("y", y)?;
+                if x[0] * y[1] - x[1] * y[0] == 0.0 {
+                    return Err("x and y must be non-zero and not parallel".to_string());
+                }
+                check_color("albedo", albedo)?;
+                check_alpha(alpha)?;
+                check_color("radiance", radiance)
+            }
+            Shape::Rectangle {
+                center,
+                half_size,
+                albedo,
+                alpha,
+                radiance,
+            } => {
+                check_vector("center", center)?;
+                check_positive("half_size", half_size[0])?;
+                check_positive("half_size", half_size[1])?;
+                check_color("albedo", albedo)?;
+                check_alpha(alpha)?;
+                check_color("radiance", radiance)
+            }
+            Shape::Circle {
+                center,
+                radius,
+                segments,
+                albedo,
+                alpha,
+                radiance,
+            } => {
+                check_vector("center", center)?;
+                check_positive("radius", radius)?;
+                check_segments(segments, 3)?;
+                check_color("albedo", albedo)?;
+                check_alpha(alpha)?;
+                check_color("radiance", radiance)
+            }
+            Shape::Ellipse {
+                center,
+                x,
+                y,
+                segments,
+                albedo,
+                alpha,
+                radiance,
+            } => {
+                check_vector("center", center)?;
+                check_vector("x", x)?;
+                check_vector("y", y)?;
+                if x[0] * y[1] - x[1] * y[0] == 0.0 {
+                    return Err("x and y must be non-zero and not parallel".to_string());
+                }
+                check_segments(segments, 3)?;
+                check_color("albedo", albedo)?;
+                check_alpha(alpha)?;
+                check_color("radiance", radiance)
+            }
+            Shape::Polygon {
+                ref points,
+                albedo,
+                alpha,
+                radiance,
+            } => {
+                if points.len() < 3 {
+                    return Err("a polygon needs at least 3 points".to_string());
+                }
+                points
+                    .iter()
+                    .try_for_each(|&point| check_vector("points", point))?;
+                let points: Vec<_> = points.iter().map(|&point| point.into()).collect();
+                if !is_simple_polygon(&points) {
+                    return Err(
+                        "a polygon needs an area and edges that don't cross or touch".to_string(),
+                    );
+                }
+                check_color("albedo", albedo)?;
+                check_alpha(alpha)?;
+                check_color("radiance", radiance)
+            }
+            Shape::Line {
+                ref points,
+                thickness,
+                albedo,
+                alpha,
+                radiance,
+            } => {
+                if points.is_empty() {
+                    return Err("a line needs at least 1 point".to_string());
+                }
+                points
+                    .iter()
+                    .try_for_each(|&point| check_vector("points", point))?;
+                check_positive("thickness", thickness)?;
+                check_color("albedo", albedo)?;
+                check_alpha(alpha)?;
+                check_color("radiance", radiance)
+            }
+            Shape::Arc {
+                center,
+                radius,
+                start,
+                end,
+                thickness,
+                segments,
+                albedo,
+                alpha,
+                radiance,
+            } => {
+                check_vector("center", center)?;
+                check_positive("radius", radius)?;
+                check_vector("start and end", [start, end])?;
+                check_positive("thickness", thickness)?;
+                check_segments(segments, 1)?;
+                check_color("albedo", albedo)?;
+                check_alpha(alpha)?;
+                check_color("radiance", radiance)
+            }
+        }
+    }
+
+    /// Adds the shape, which fails only if it is invalid.
+    fn add_to(&self, vertices: &mut VertexList) -> Result<(), ShapeError> {
+        let albedo = |color: Color, alpha: f32| Srgba::new(color.0, color.1, color.2, alpha);
+        match *self {
+            Shape::Triangle { vertices: triangle } => {
+                vertices.triangle(triangle.map(|vertex| {
+                    let albedo = albedo(vertex.albedo, vertex.alpha).into_linear();
+                    Vertex {
+                        position: vertex.position.into(),
+                        normal: vertex.normal.into(),
+                        albedo,
+                        radiance: Alpha {
+                            color: Srgb::from(vertex.radiance).into_linear(),
+                            alpha: albedo.alpha,
+                        },
+                    }
+                }));
+            }
+            Shape::Parallelogram {
+                center,
+                x,
+                y,
+                albedo: color,
+                alpha,
+                radiance,
+            } => {
+                vertices.parallelogram(
+                    center.into(),
+                    x.into(),
+                    y.into(),
+                    albedo(color, alpha),
+                    Srgb::from(radiance),
+                )?;
+            }
+            Shape::Rectangle {
+                center,
+                half_size,
+                albedo: color,
+                alpha,
+                radiance,
+            } => {
+                vertices.rectangle(
+                    center.into(),
+                    half_size.into(),
+                    albedo(color, alpha),
+                    Srgb::from(radiance),
+                )?;
+            }
+            Shape::Circle {
+                center,
+                radius,
+                segments,
+                albedo: color,
+                alpha,
+                radiance,
+            } => {
+                vertices.circle(
+                    center.into(),
+                    radius,
+                    segments,
+                    albedo(color, alpha),
+                    Srgb::from(radiance),
+                )?;
+            }
+            Shape::Ellipse {
+                center,
+                x,
+                y,
+                segments,
+                albedo: color,
+                alpha,
+                radiance,
+            } => {
+                vertices.ellipse(
+                    center.into(),
+                    x.into(),
+                    y.into(),
+                    segments,
+                    albedo(color, alpha),
+                    Srgb::from(radiance),
+                )?;
+            }
+            Shape::Polygon {
+                ref points,
+                albedo: color,
+                alpha,
+                radiance,
+            } => {
+                let points: Vec<_> = points.iter().map(|&point| point.into()).collect();
+                vertices.polygon(&points, albedo(color, alpha), Srgb::from(radiance))?;
+            }
+            Shape::Line {
+                ref points,
+                thickness,
+                albedo: color,
+                alpha,
+                radiance,
+            } => {
+                let points: Vec<_> = points.iter().map(|&point| point.into()).collect();
+                vertices.polyline(
+                    &points,
+                    thickness,
+                    albedo(color, alpha),
+                    Srgb::from(radiance),
+                )?;
+            }
+            Shape::Arc {
+                center,
+                radius,
+                start,
+                end,
+                thickness,
+                segments,
+                albedo: color,
+                alpha,
+                radiance,
+            } => {
+                vertices.arc(
+                    center.into(),
+                    radius,
+                    start.to_radians(),
+                    end.to_radians(),
+                    thickness,
+                    segments,
+                    albedo(color, alpha),
+                    Srgb::from(radiance),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SceneAnimation {
+    fn validate(&self) -> Result<(), String> {
+        let period = match *self {
+            SceneAnimation::Swing {
+                pivot,
+                angle,
+                period,
+            } => {
+                check_vector("pivot", pivot)?;
+                if !angle.is_finite() {
+                    return Err("angle must be finite".to_string());
+                }
+                period
+            }
+            SceneAnimation::Slide { offset, period } => {
+                check_vector("offset", offset)?;
+                period
+            }
+            SceneAnimation::Pulse { min, max, period } => {
+                if !(min.is_finite() && max.is_finite() && min >= 0.0 && max >= 0.0) {
+                    return Err("min and max must be finite and non-negative".to_string());
+                }
+                period
+            }
+        };
+        if period.is_finite() && period > 0.0 {
+            Ok(())
+        } else {
+            Err(format!("period must be above 0, got {}", period))
+        }
+    }
+}
+
+impl SceneObject {
+    fn validate(&self) -> Result<(), String> {
+        for (i, shape) in self.shapes.iter().enumerate() {
+            shape
+                .validate()
+                .map_err(|reason| format!("shape {}: {}", i, reason))?;
+        }
+        check_vector("translation", self.transform.translation)?;
+        check_vector("scale", self.transform.scale)?;
+        if !self.transform.rotation.is_finite() {
+            return Err("rotation must be finite".to_string());
+        }
+        check_color("albedo", self.albedo)?;
+        check_color("emission", self.emission)?;
+        self.animations
+            .iter()
+            .try_for_each(SceneAnimation::validate)
+    }
+
+    /// The transform and colors of the object `time` seconds into its animations.
+    pub fn properties_at(&self, time: f32) -> ObjectProperties {
+        let SceneTransform {
+            translation,
+            rotation,
+            scale,
+        } = self.transform;
+        let mut transform = Affine2::from_matrix_unchecked(
+            Isometry2::new(translation.into(), rotation.to_radians()).to_homogeneous()
+                * Matrix3::new_nonuniform_scaling(&Vector2::from(scale)),
+        );
+        let mut emission = Srgb::from(self.emission).into_linear();
+
+        for animation in &self.animations {
+            match *animation {
+                SceneAnimation::Swing {
+                    pivot,
+                    angle,
+                    period,
+                } => {
+                    let pivot = Translation2::from(Vector2::from(pivot));
+                    let rotation = Rotation2::new(angle.to_radians() * (time / period * TAU).sin());
+                    transform = pivot * rotation * pivot.inverse() * transform;
+                }
+                SceneAnimation::Slide { offset, period } => {
+                    let offset = Vector2::from(offset) * (time / period * TAU).sin();
+                    transform = Translation2::from(offset) * transform;
+                }
+                SceneAnimation::Pulse { min, max, period } => {
+                    let t = 0.5 + 0.5 * (time / period * TAU).sin();
+                    emission *= min + (max - min) * t;
+                }
+            }
+        }
+
+        ObjectProperties {
+            transform,
+            albedo: Srgb::from(self.albedo).into_linear().into(),
+            emission,
+        }
+    }
+
+    pub fn object(&self) -> Object {
+        let mut mesh = VertexList::new();
+        for shape in &self.shapes {
+            // Loading rejects invalid shapes, so only objects made in code can lose any here.
+            let _ = shape.add_to(&mut mesh);
+        }
+        Object {
+            name: self.name.clone(),
+            mesh: mesh.finish(),
+            properties: self.properties_at(0.0),
+        }
+    }
+}
+
+impl SceneEnvironment {
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            SceneEnvironment::Ambient(radiance) => check_color("radiance", *radiance),
+            SceneEnvironment::Sun {
+                direction,
+                angular_size,
+                radiance,
+            } => {
+                if !direction.is_finite() {
+                    return Err("direction must be finite".to_string());
+                }
+                if !(*angular_size > 0.0 && *angular_size <= 360.0) {
+                    return Err(format!(
+                        "angular_size must be above 0 and at most 360, got {}",
+                        angular_size
+                    ));
+                }
+                check_color("radiance", *radiance)
+            }
+            SceneEnvironment::Map(samples) => {
+                if samples.is_empty() {
+                    return Err("map must have at least one sample".to_string());
+                }
+                samples
+                    .iter()
+                    .try_for_each(|sample| check_color("sample", *sample))
+            }
+        }
+    }
+
+    pub fn environment(&self) -> Environment {
+        let linear = |color: Color| Srgb::from(color).into_linear();
+        match self {
+            SceneEnvironment::Ambient(radiance) => Environment::Ambient(linear(*radiance)),
+            SceneEnvironment::Sun {
+                direction,
+                angular_size,
+                radiance,
+            } => Environment::Sun {
+                direction: direction.to_radians(),
+                angular_size: angular_size.to_radians(),
+                radiance: linear(*radiance),
+            },
+            SceneEnvironment::Map(samples) => {
+                Environment::Map(samples.iter().map(|sample| linear(*sample)).collect())
+            }
+        }
+    }
+}
+
+impl SceneBitmap {
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            SceneBitmap::Images {
+                origin,
+                texel_size,
+                emission_intensity,
+                normal,
+                normals_from_alpha,
+                ..
+            } => {
+                check_vector("origin", *origin)?;
+                check_positive("texel_size", *texel_size)?;
+                if !(emission_intensity.is_finite() && *emission_intensity >= 0.0) {
+                    return Err("emission_intensity must be finite and non-negative".to_string());
+                }
+                if normal.is_some() && *normals_from_alpha {
+                    return Err("normal and normals_from_alpha can't be used together".to_string());
+                }
+                Ok(())
+            }
+            SceneBitmap::Tilemap {
+                origin,
+                tile_size,
+                palette,
+                rows,
+                ..
+            } => {
+                check_vector("origin", *origin)?;
+                check_positive("tile_size", *tile_size)?;
+                for (tile, properties) in palette {
+                    check_color("albedo", properties.albedo)
+                        .and_then(|()| check_alpha(properties.alpha))
+                        .and_then(|()| check_color("radiance", properties.radiance))
+                        .map_err(|reason| format!("tile {:?}: {}", tile, reason))?;
+                }
+                let width = rows.first().map_or(0, |row| row.chars().count());
+                if width == 0 {
+                    return Err("the tilemap must have at least one tile".to_string());
+                }
+                for (y, row) in rows.iter().enumerate() {
+                    if row.chars().count() != width {
+                        return Err(format!("row {} must be {} tiles long", y, width));
+                    }
+                    if let Some(tile) = row.chars().find(|c| *c != ' ' && !palette.contains_key(c))
+                    {
+                        return Err(format!("row {}: tile {:?} is not in the palette", y, tile));
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Builds the texels, reading images relative to `directory`.
+    fn load(&self, directory: &Path) -> Result<Bitmap, BitmapError> {
+        match self {
+            SceneBitmap::Images {
+                origin,
+                texel_size,
+                albedo,
+                emission,
+                emission_intensity,
+                normal,
+                normals_from_alpha,
+            } => {
+                let emission = emission.as_ref().map(|path| directory.join(path));
+                let mut bitmap = Bitmap::load(
+                    &directory.join(albedo),
+                    emission.as_deref().map(|path| (path, *emission_intensity)),
+                    normal.as_ref().map(|path| directory.join(path)).as_deref(),
+                    Vector2::from(*origin),
+                    *texel_size,
+                )?;
+                if *normals_from_alpha {
+                    bitmap.derive_normals();
+                }
+                Ok(bitmap)
+            }
+            SceneBitmap::Tilemap {
+                origin,
+                tile_size,
+                palette,
+                rows,
+                normals_from_alpha,
+            } => {
+                let size = Vector2::new(rows[0].chars().count(), rows.len()).cast();
+                let mut bitmap = Bitmap::new(size, Vector2::from(*origin), *tile_size);
+                let tiles = rows.iter().flat_map(|row| row.chars());
+                for (i, tile) in tiles.enumerate() {
+                    if let Some(tile) = palette.get(&tile) {
+                        let Color(r, g, b) = tile.albedo;
+                        bitmap.albedo[i] = Srgba::new(r, g, b, tile.alpha).into_linear();
+                        bitmap.emission[i] = Srgb::from(tile.radiance).into_linear();
+                    }
+                }
+                if *normals_from_alpha {
+                    bitmap.derive_normals();
+                }
+                Ok(bitmap)
+            }
+        }
+    }
+}
+
+impl Scene {
+    /// Loads a RON scene, or the shapes of an SVG file if the extension is `svg`. SVG curves are
+    /// flattened to within `curve_tolerance` logical pixels.
+    pub fn load(path: impl AsRef<Path>, curve_tolerance: f32) -> Result<Self, SceneError> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path).map_err(|error| SceneError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        let is_svg =
+            matches!(path.extension(), Some(extension) if extension.eq_ignore_ascii_case("svg"));
+        let mut scene = if is_svg {
+            Scene {
+                shapes: svg::import(&source, curve_tolerance).map_err(|error| SceneError::Svg {
+                    path: path.to_path_buf(),
+                    error,
+                })?,
+                ..Scene::default()
+            }
+        } else {
+            ron::from_str(&source).map_err(|error| SceneError::Parse {
+                path: path.to_path_buf(),
+                error,
+            })?
+        };
+        scene
+            .environment
+            .validate()
+            .map_err(|reason| SceneError::InvalidEnvironment {
+                path: path.to_path_buf(),
+                reason,
+            })?;
+        for (i, shape) in scene.shapes.iter().enumerate() {
+            shape.validate().map_err(|reason| SceneError::Invalid {
+                path: path.to_path_buf(),
+                shape: i,
+                reason,
+            })?;
+        }
+        for object in &scene.objects {
+            object
+                .validate()
+                .map_err(|reason| SceneError::InvalidObject {
+                    path: path.to_path_buf(),
+                    object: object.name.clone(),
+                    reason,
+                })?;
+        }
+        if let Some(bitmap) = &scene.bitmap {
+            bitmap
+                .validate()
+                .map_err(|reason| SceneError::InvalidBitmap {
+                    path: path.to_path_buf(),
+                    reason,
+                })?;
+            let directory = path.parent().unwrap_or_else(|| Path::new(""));
+            scene.texels = Some(bitmap.load(directory).map_err(|error| SceneError::Bitmap {
+                path: path.to_path_buf(),
+                error,
+            })?);
+        }
+        Ok(scene)
+    }
+
+    /// The images the bitmap of the scene loaded from `path` is read from.
+    pub fn image_paths(&self, path: &Path) -> Vec<PathBuf> {
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+        match &self.bitmap {
+            Some(SceneBitmap::Images {
+                albedo,
+                emission,
+                normal,
+                ..
+            }) => [Some(albedo), emission.as_ref(), normal.as_ref()]
+                .into_iter()
+                .flatten()
+                .map(|image| directory.join(image))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Writes the scene as RON. SVG scenes are written as the shapes they were imported as.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SceneError> {
+        let path = path.as_ref();
+        let source =
+            ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new()).map_err(|error| {
+                SceneError::Serialize {
+                    path: path.to_path_buf(),
+                    error,
+                }
+            })?;
+        std::fs::write(path, source).map_err(|error| SceneError::Io {
+            path: path.to_path_buf(),
+            error,
+        })
+    }
+
+    pub fn vertices(&self) -> VertexList {
+        let mut vertices = VertexList::new();
+        for shape in &self.shapes {
+            // Loading rejects invalid shapes, so only scenes made in code can lose any here.
+            let _ = shape.add_to(&mut vertices);
+        }
+        vertices.finish()
+    }
+
+    /// The loaded `bitmap`.
+    pub fn texels(&self) -> Option<&Bitmap> {
+        self.texels.as_ref()
+    }
+
+    pub fn objects(&self) -> Vec<Object> {
+        self.objects.iter().map(SceneObject::object).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::svg::DEFAULT_TOLERANCE;
+
+    /// A new directory in the temporary directory, named after the test writing it and the
+    /// process, so concurrent test runs don't share it.
+    fn temporary_dir(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("radiance-scene-{}-{}", std::process::id(), name));
+        std::fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    /// A file in the `temporary_dir` of `name`.
+    fn temporary_file(name: &str, source: &str) -> PathBuf {
+        let path = temporary_dir(name).join(name);
+        std::fs::write(&path, source).unwrap();
+        path
+    }
+
+    fn load_source(name: &str, source: &str) -> Result<Scene, SceneError> {
+        let path = temporary_file(name, source);
+        let scene = Scene::load(&path, DEFAULT_TOLERANCE);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        scene
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        for path in [
+            "scenes/default.ron",
+            "scenes/tilemap.ron",
+            "scenes/layout.svg",
+        ] {
+            let scene = Scene::load(path, DEFAULT_TOLERANCE).unwrap();
+            let dir = temporary_dir("round-trip");
+            // Bitmaps are found relative to the scene, so they go along with it.
+            let scene_dir = Path::new(path).parent().unwrap();
+            for image in scene.image_paths(Path::new(path)) {
+                let copy = dir.join(image.strip_prefix(scene_dir).unwrap());
+                std::fs::create_dir_all(copy.parent().unwrap()).unwrap();
+                std::fs::copy(&image, copy).unwrap();
+            }
+            let saved = dir.join("scene.ron");
+            scene.save(&saved).unwrap();
+            let loaded = Scene::load(&saved, DEFAULT_TOLERANCE);
+            assert_eq!(loaded.unwrap(), scene, "{}", path);
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+    }
+
+    #[test]
+    fn defaults_are_optional() {
+        let scene = load_source(
+            "defaults.ron",
+            "(shapes: [Circle(center: (1.0, 2.0), radius: 3.0, albedo: (0.5, 0.5, 0.5))])",
+        )
+        .unwrap();
+        assert_eq!(scene.shapes, vec![Shape::Circle {
+            center: [1.0, 2.0],
+            radius: 3.0,
+            segments: 32,
+            albedo: Color(0.5, 0.5, 0.5),
+            alpha: 1.0,
+            radiance: Color::default(),
+        }]);
+        assert_eq!(scene.environment, SceneEnvironment::default());
+        assert!(scene.objects.is_empty());
+    }
+
+    #[test]
+    fn malformed_scenes_are_errors() {
+        assert!(matches!(
+            load_source("syntax.ron", "(shapes: [Circle(center: (1.0, 2.0)"),
+            Err(SceneError::Parse { .. })
+        ));
+        assert!(matches!(
+            load_source("unknown.ron", "(shapes: [Hexagon(center: (1.0, 2.0))])"),
+            Err(SceneError::Parse { .. })
+        ));
+        assert!(matches!(
+            Scene::load("scenes/missing.ron", DEFAULT_TOLERANCE),
+            Err(SceneError::Io { .. })
+        ));
+    }
+
+    #[test]
+    fn image_paths_are_relative_to_the_scene() {
+        let scene = Scene {
+            bitmap: Some(SceneBitmap::Images {
+                origin: [0.0, 0.0],
+                texel_size: 1.0,
+                albedo: PathBuf::from("albedo.png"),
+                emission: None,
+                emission_intensity: 1.0,
+                normal: Some(PathBuf::from("maps/normal.png")),
+                normals_from_alpha: false,
+            }),
+            ..Scene::default()
+        };
+        assert_eq!(scene.image_paths(Path::new("scenes/level.ron")), vec![
+            PathBuf::from("scenes/albedo.png"),
+            PathBuf::from("scenes/maps/normal.png")
+        ]);
+        assert!(Scene::default()
+            .image_paths(Path::new("scenes/level.ron"))
+            .is_empty());
+    }
+
+    #[test]
+    fn invalid_shapes_are_reported_by_index() {
+        let error = load_source(
+            "invalid.ron",
+            "(shapes: [
+                Circle(center: (0.0, 0.0), radius: 1.0, albedo: (1.0, 1.0, 1.0)),
+                Circle(center: (0.0, 0.0), radius: -1.0, albedo: (1.0, 1.0, 1.0)),
+            ])",
+        );
+        assert!(matches!(error, Err(SceneError::Invalid { shape: 1, .. })));
+
+        let error = load_source(
+            "bowtie.ron",
+            "(shapes: [Polygon(
+                points: [(0.0, 0.0), (1.0, 1.0), (1.0, 0.0), (0.0, 1.0)],
+                albedo: (1.0, 1.0, 1.0),
+            )])",
+        );
+        assert!(matches!(error, Err(SceneError::Invalid { shape: 0, .. })));
+
+        let error = load_source(
+            "parallel.ron",
+            "(shapes: [Parallelogram(
+                center: (0.0, 0.0), x: (1.0, 1.0), y: (2.0, 2.0), albedo: (1.0, 1.0, 1.0),
+            )])",
+        );
+        assert!(matches!(error, Err(SceneError::Invalid { shape: 0, .. })));
+
+        let error = load_source(
+            "negative.ron",
+            "(shapes: [Rectangle(
+                center: (0.0, 0.0), half_size: (1.0, -1.0), albedo: (1.0, 1.0, 1.0),
+            )])",
+        );
+        assert!(matches!(error, Err(SceneError::Invalid { shape: 0, .. })));
+    }
+}
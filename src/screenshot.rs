@@ -0,0 +1,107 @@
+use palette::{LinSrgba, Srgba};
+use std::fmt::{self, Display, Formatter};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::readback::ImageBuffer;
+
+#[derive(Debug)]
+pub enum ScreenshotError {
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    Png {
+        path: PathBuf,
+        error: png::EncodingError,
+    },
+}
+
+impl Display for ScreenshotError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ScreenshotError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            ScreenshotError::Png { path, error } => write!(f, "{}: {}", path.display(), error),
+        }
+    }
+}
+
+impl std::error::Error for ScreenshotError {}
+
+/// Writes linear radiance losslessly as a Portable Float Map. Alpha is dropped.
+pub fn write_pfm(
+    path: impl AsRef<Path>,
+    image: &ImageBuffer<LinSrgba>,
+) -> Result<(), ScreenshotError> {
+    let path = path.as_ref();
+    let io_error = |error| ScreenshotError::Io {
+        path: path.to_path_buf(),
+        error,
+    };
+
+    let mut writer = BufWriter::new(File::create(path).map_err(io_error)?);
+    // A negative scale marks the data as little endian.
+    write!(writer, "PF\n{} {}\n-1.0\n", image.size.x, image.size.y).map_err(io_error)?;
+    // PFM rows go from the bottom up.
+    for row in image.pixels.chunks_exact(image.size.x as usize).rev() {
+        for pixel in row {
+            for channel in [pixel.red, pixel.green, pixel.blue] {
+                writer.write_all(&channel.to_le_bytes()).map_err(io_error)?;
+            }
+        }
+    }
+    writer.flush().map_err(io_error)
+}
+
+/// Writes an 8-bit sRGB PNG. Alpha is dropped, as the displayed frame is opaque.
+pub fn write_png(
+    path: impl AsRef<Path>,
+    image: &ImageBuffer<Srgba<u8>>,
+) -> Result<(), ScreenshotError> {
+    let path = path.as_ref();
+    let file = File::create(path).map_err(|error| ScreenshotError::Io {
+        path: path.to_path_buf(),
+        error,
+    })?;
+    let png_error = |error| ScreenshotError::Png {
+        path: path.to_path_buf(),
+        error,
+    };
+
+    let mut encoder = png::Encoder::new(BufWriter::new(file), image.size.x, image.size.y);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(png_error)?;
+    let data: Vec<u8> = image
+        .pixels
+        .iter()
+        .flat_map(|pixel| [pixel.red, pixel.green, pixel.blue])
+        .collect();
+    writer.write_image_data(&data).map_err(png_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::vector;
+
+    #[test]
+    fn png_is_opaque() {
+        let path = std::env::temp_dir().join(format!("radiance-png-{}.png", std::process::id()));
+        let image = ImageBuffer {
+            size: vector![2, 1],
+            // Like the nearly transparent background of the default scene.
+            pixels: vec![Srgba::new(10, 20, 30, 3), Srgba::new(200, 100, 50, 255)],
+        };
+        write_png(&path, &image).unwrap();
+
+        let decoder = png::Decoder::new(File::open(&path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(info.color_type, png::ColorType::Rgb);
+        assert_eq!(&data[..info.buffer_size()], &[10, 20, 30, 200, 100, 50]);
+    }
+}
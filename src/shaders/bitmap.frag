@@ -0,0 +1,45 @@
+#version 460
+
+layout (location = 0) in vec2 o_position;
+layout (location = 0) out vec4 f_albedo_lin;
+layout (location = 1) out vec4 f_radiance_lin;
+layout (location = 2) out vec2 f_normal;
+
+layout (set = 0, binding = 0) uniform GlobalUniforms {
+    vec2 window_size;
+    vec2 surface_size;
+    vec2 view_size;
+    vec2 camera_position;
+    float camera_zoom;
+    float camera_rotation;
+};
+
+// Must match `BitmapUniforms`.
+layout (set = 1, binding = 0) uniform BitmapUniforms {
+    vec2 origin;
+    float texel_size;
+};
+layout (set = 1, binding = 1) uniform texture2D t_albedo_lin;
+layout (set = 1, binding = 2) uniform texture2D t_radiance_lin;
+layout (set = 1, binding = 3) uniform texture2D t_normal;
+
+void main() {
+    // Undo the camera transform of `prerender.vert`, to find the world point of the fragment.
+    vec2 view = o_position * view_size / 2.0;
+    float c = cos(camera_rotation);
+    float s = sin(camera_rotation);
+    vec2 world_position = mat2(c, s, -s, c) * view / camera_zoom + camera_position;
+
+    ivec2 size = textureSize(t_albedo_lin, 0);
+    ivec2 texel = ivec2(floor((world_position - origin) / texel_size));
+    // Rows go down from the top, like images.
+    texel.y = size.y - 1 - texel.y;
+    if (any(lessThan(texel, ivec2(0))) || any(greaterThanEqual(texel, size))) {
+        discard;
+    }
+    f_albedo_lin = texelFetch(t_albedo_lin, texel, 0);
+    f_radiance_lin = texelFetch(t_radiance_lin, texel, 0);
+    // The radiance is traced in view space, so turn the normal the other way of the camera,
+    // like `prerender.vert` does.
+    f_normal = mat2(c, -s, s, c) * texelFetch(t_normal, texel, 0).xy;
+}
@@ -0,0 +1,30 @@
+// Log2 luminance range covered by the histogram. Bin 0 holds the black pixels.
+const float HISTOGRAM_MIN_LOG_LUMINANCE = -12.0;
+const float HISTOGRAM_LOG_LUMINANCE_RANGE = 20.0;
+const uint HISTOGRAM_BINS = 256;
+const uint HISTOGRAM_TILE_SIZE = 16;
+
+layout (set = 1, binding = 0) uniform ExposureUniforms {
+    float delta_time;
+    float adaptation_speed;
+    float min_ev;
+    float max_ev;
+    uint reset_adaptation;
+};
+
+layout (std430, set = 2, binding = 1) buffer TileHistograms {
+    uint[] tile_histograms;
+};
+
+layout (std430, set = 2, binding = 2) buffer Exposure {
+    float average_log_luminance;
+    float exposure_ev;
+};
+
+uint tiles_per_row() {
+    return (uint(window_size.x) + HISTOGRAM_TILE_SIZE - 1) / HISTOGRAM_TILE_SIZE;
+}
+
+uint num_tiles() {
+    return tiles_per_row() * ((uint(window_size.y) + HISTOGRAM_TILE_SIZE - 1) / HISTOGRAM_TILE_SIZE);
+}
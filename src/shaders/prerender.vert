@@ -10,11 +10,45 @@ layout (location = 2) out vec2 o_normal;
 
 layout (set = 0, binding = 0) uniform GlobalUniforms {
     vec2 window_size;
+    vec2 surface_size;
+    vec2 view_size;
+    vec2 camera_position;
+    float camera_zoom;
+    float camera_rotation;
 };
 
+// Must match `ObjectUniforms`. Bound at the offset of the object being drawn.
+layout (set = 1, binding = 0) uniform ObjectUniforms {
+    vec2 transform_x;
+    vec2 transform_y;
+    vec2 translation;
+    vec2 normal_x;
+    vec2 normal_y;
+    vec2 _padding;
+    vec4 albedo;
+    vec4 emission;
+} object;
+
 void main() {
-    gl_Position = vec4(position / window_size * 2.0 - vec2(1.0), 0.0, 1.0);
-    o_albedo_lin = albedo_lin;
-    o_radiance_lin = radiance_lin;
-    o_normal = normal;
+    mat2 transform = mat2(object.transform_x, object.transform_y);
+    vec2 world_position = transform * position + object.translation;
+    vec2 world_normal = mat2(object.normal_x, object.normal_y) * normal;
+
+    // The world turns the opposite way of the camera.
+    float c = cos(camera_rotation);
+    float s = sin(camera_rotation);
+    mat2 world_to_view = mat2(c, -s, s, c);
+    vec2 view = world_to_view * (world_position - camera_position) * camera_zoom;
+    // View coordinates are in logical pixels, so the scene covers the same area at any
+    // resolution.
+    gl_Position = vec4(view / view_size * 2.0, 0.0, 1.0);
+    o_albedo_lin = albedo_lin * object.albedo;
+    // The radiance shares its alpha with the albedo.
+    o_radiance_lin = vec4(radiance_lin.rgb * object.emission.rgb, radiance_lin.a * object.albedo.a);
+    // Keep the length of the mesh normal, which is zero for surfaces without one. The radiance
+    // is traced in view space, so the normal turns with the camera too.
+    float world_length = length(world_normal);
+    o_normal = world_length > 0.0
+        ? world_to_view * world_normal * (length(normal) / world_length)
+        : vec2(0.0);
 }
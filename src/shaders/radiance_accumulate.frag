@@ -0,0 +1,22 @@
+#version 460
+#extension GL_EXT_samplerless_texture_functions : enable
+
+layout (location = 0) out vec4 f_history;
+
+layout (set = 0, binding = 0) uniform GlobalUniforms {
+    vec2 window_size;
+};
+
+layout (set = 1, binding = 0) uniform AccumulateUniforms {
+    float weight;
+};
+
+layout (set = 2, binding = 0) uniform texture2D t_total_radiance;
+layout (set = 2, binding = 1) uniform texture2D t_previous_history;
+
+void main() {
+    ivec2 position = ivec2(gl_FragCoord.xy);
+    vec4 previous = texelFetch(t_previous_history, position, 0);
+    vec4 current = texelFetch(t_total_radiance, position, 0);
+    f_history = mix(previous, current, weight);
+}
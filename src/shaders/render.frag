@@ -1,18 +1,197 @@
 #version 460
 #extension GL_EXT_samplerless_texture_functions : enable
+#include "rgb9e5_format.glsl"
 
 layout (location = 0) out vec4 f_color;
 
 layout (set = 0, binding = 0) uniform GlobalUniforms {
     vec2 window_size;
+    vec2 surface_size;
 };
 
 layout (set = 1, binding = 0) uniform texture2D t_total_radiance;
 
-vec4 into_srgb(vec4 linear) {
-    return vec4(pow(linear.xyz, vec3(1.0 / 2.2)), linear.w);
+layout (set = 2, binding = 0) uniform RenderUniforms {
+    float exposure;
+    uint tone_mapping;
+    uint encode_srgb;
+    uint auto_exposure;
+    uint view_mode;
+    uint direction_layer;
+    uint upsampling;
+};
+
+layout (std430, set = 3, binding = 0) readonly buffer Exposure {
+    float average_log_luminance;
+    float exposure_ev;
+};
+
+layout (set = 4, binding = 0) uniform texture2D t_albedo;
+layout (set = 4, binding = 1) uniform texture2D t_radiance;
+layout (set = 4, binding = 2) uniform texture2D t_normal;
+layout (set = 4, binding = 3) uniform utexture2DArray t_directional_radiance;
+// Albedo at the surface resolution.
+layout (set = 4, binding = 4) uniform texture2D t_guide_albedo;
+
+const uint TONE_MAPPING_LINEAR = 0;
+const uint TONE_MAPPING_REINHARD = 1;
+const uint TONE_MAPPING_ACES = 2;
+const uint TONE_MAPPING_AGX = 3;
+
+const uint VIEW_MODE_FINAL = 0;
+const uint VIEW_MODE_ALBEDO = 1;
+const uint VIEW_MODE_EMITTED = 2;
+const uint VIEW_MODE_NORMAL = 3;
+const uint VIEW_MODE_ALPHA = 4;
+const uint VIEW_MODE_DIRECTIONAL_RADIANCE = 5;
+const uint VIEW_MODE_HEATMAP = 6;
+
+const uint UPSAMPLING_NEAREST = 0;
+const uint UPSAMPLING_BILINEAR = 1;
+const uint UPSAMPLING_EDGE_AWARE = 2;
+
+// How quickly texels lose weight as their albedo departs from the guide.
+const float EDGE_SIGMA = 0.1;
+
+// Log2 luminance range spread over the heatmap colors.
+const float HEATMAP_MIN_EV = -8.0;
+const float HEATMAP_MAX_EV = 8.0;
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve.
+vec3 aces(vec3 x) {
+    return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+}
+
+// Polynomial fit of the default AgX contrast curve, by Benjamin Wrensch.
+const mat3 AGX_INSET = mat3(
+    0.842479062253094, 0.0423282422610123, 0.0423756549057051,
+    0.0784335999999992, 0.878468636469772, 0.0784336,
+    0.0792237451477643, 0.0791661274605434, 0.879142973793104
+);
+const mat3 AGX_OUTSET = mat3(
+    1.19687900512017, -0.0528968517574562, -0.0529716355144438,
+    -0.0980208811401368, 1.15190312990417, -0.0980434501171241,
+    -0.0990297440797205, -0.0989611768448433, 1.15107367264116
+);
+const float AGX_MIN_EV = -12.47393;
+const float AGX_MAX_EV = 4.026069;
+
+vec3 agx(vec3 x) {
+    x = AGX_INSET * x;
+    x = clamp(log2(max(x, vec3(1e-10))), AGX_MIN_EV, AGX_MAX_EV);
+    x = (x - AGX_MIN_EV) / (AGX_MAX_EV - AGX_MIN_EV);
+    vec3 x2 = x * x;
+    vec3 x4 = x2 * x2;
+    x = 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232;
+    // The curve outputs display encoded values, so decode them to go through the usual encode.
+    return pow(max(AGX_OUTSET * x, vec3(0.0)), vec3(2.2));
+}
+
+vec3 tone_map(vec3 x) {
+    switch (tone_mapping) {
+        case TONE_MAPPING_REINHARD:
+            return x / (1.0 + x);
+        case TONE_MAPPING_ACES:
+            return aces(x);
+        case TONE_MAPPING_AGX:
+            return agx(x);
+        default:
+            return clamp(x, 0.0, 1.0);
+    }
+}
+
+vec3 into_srgb(vec3 linear) {
+    vec3 low = linear * 12.92;
+    vec3 high = 1.055 * pow(linear, vec3(1.0 / 2.4)) - 0.055;
+    return mix(low, high, greaterThan(linear, vec3(0.0031308)));
+}
+
+// Blue through cyan, green and yellow to red.
+vec3 heatmap(float t) {
+    t = clamp(t, 0.0, 1.0) * 4.0;
+    return clamp(vec3(t - 2.0, t < 2.0 ? t : 4.0 - t, 2.0 - t), 0.0, 1.0);
+}
+
+// Interpolates the four texels around the fragment. The filtering is done by hand, as float
+// textures can't be sampled with filtering.
+vec4 upsample_radiance(ivec2 nearest) {
+    if (upsampling == UPSAMPLING_NEAREST || window_size == surface_size) {
+        return texelFetch(t_total_radiance, nearest, 0);
+    }
+
+    vec2 source = gl_FragCoord.xy * window_size / surface_size - 0.5;
+    ivec2 base = ivec2(floor(source));
+    vec2 fraction = source - vec2(base);
+    vec4 guide = texelFetch(t_guide_albedo, ivec2(gl_FragCoord.xy), 0);
+
+    vec4 sum = vec4(0.0);
+    float total_weight = 0.0;
+    for (int i = 0; i < 4; i++) {
+        ivec2 offset = ivec2(i & 1, i >> 1);
+        ivec2 texel = clamp(base + offset, ivec2(0), ivec2(window_size) - 1);
+        vec2 axis_weights = mix(1.0 - fraction, fraction, vec2(offset));
+        float weight = axis_weights.x * axis_weights.y;
+        if (upsampling == UPSAMPLING_EDGE_AWARE) {
+            vec4 difference = texelFetch(t_albedo, texel, 0) - guide;
+            weight *= exp(-dot(difference, difference) / (EDGE_SIGMA * EDGE_SIGMA));
+        }
+        sum += texelFetch(t_total_radiance, texel, 0) * weight;
+        total_weight += weight;
+    }
+    // No texel is like the guide, e.g. on features thinner than a texel.
+    if (total_weight < 1e-6) {
+        return texelFetch(t_total_radiance, nearest, 0);
+    }
+    return sum / total_weight;
 }
 
 void main() {
-    f_color = into_srgb(texelFetch(t_total_radiance, ivec2(gl_FragCoord.xy), 0));
+    // The texel under the fragment, for the view modes that aren't upsampled.
+    ivec2 position = ivec2(gl_FragCoord.xy * window_size / surface_size);
+    vec4 radiance = upsample_radiance(position);
+    float scale = exposure;
+    if (auto_exposure != 0) {
+        scale *= exp2(exposure_ev);
+    }
+
+    vec3 color;
+    switch (view_mode) {
+        case VIEW_MODE_ALBEDO:
+            color = texelFetch(t_albedo, position, 0).xyz;
+            break;
+        case VIEW_MODE_EMITTED:
+            color = tone_map(texelFetch(t_radiance, position, 0).xyz * scale);
+            break;
+        case VIEW_MODE_NORMAL:
+            color = vec3(texelFetch(t_normal, position, 0).xy * 0.5 + 0.5, 0.5);
+            break;
+        case VIEW_MODE_ALPHA:
+            color = vec3(texelFetch(t_albedo, position, 0).w);
+            break;
+        case VIEW_MODE_DIRECTIONAL_RADIANCE: {
+            // Each direction carries its share of the light, so scale it up to the brightness
+            // it would have if all of the light came from there.
+            int layers = textureSize(t_directional_radiance, 0).z;
+            int layer = min(int(direction_layer), layers - 1);
+            uint encoded = texelFetch(t_directional_radiance, ivec3(position, layer), 0).x;
+            color = tone_map(from_rgb9e5(encoded) * float(layers) * scale);
+            break;
+        }
+        case VIEW_MODE_HEATMAP: {
+            float luminance = dot(radiance.xyz * scale, vec3(0.2126, 0.7152, 0.0722));
+            float ev = log2(max(luminance, 1e-10));
+            color = heatmap((ev - HEATMAP_MIN_EV) / (HEATMAP_MAX_EV - HEATMAP_MIN_EV));
+            break;
+        }
+        default:
+            color = tone_map(radiance.xyz * scale);
+            break;
+    }
+
+    // Surfaces with an *Srgb format encode on write.
+    if (encode_srgb != 0) {
+        color = into_srgb(color);
+    }
+    // The albedo alpha only says how much light a surface stops, so the output is opaque.
+    f_color = vec4(color, 1.0);
 }
@@ -1,47 +1,158 @@
 use nalgebra::{vector, Vector2};
+use palette::{LinSrgba, Srgba};
+use std::fmt::{self, Display, Formatter};
+use std::path::Path;
 use wgpu::util::{BufferInitDescriptor, DeviceExt};
 use wgpu::*;
+use winit::dpi::PhysicalSize;
 use winit::window::Window;
 
+use crate::bitmap::Bitmap;
+use crate::camera::Camera;
+use crate::environment::Environment;
+use crate::object::{Object, ObjectProperties};
+use crate::readback::{read_texture, ImageBuffer};
+use crate::reference::{self, PrerenderImages};
+use crate::screenshot::{self, ScreenshotError};
+use crate::texture::TextureWithView;
 use crate::uniform::{GlobalUniforms, UniformData};
 use crate::vertex::VertexList;
 
+pub mod accumulate;
+use accumulate::AccumulateState;
+pub mod exposure;
+use exposure::ExposureState;
 pub mod prerender;
 use prerender::PrerenderState;
 pub mod radiance;
-use radiance::{RadianceSettings, RadianceState};
+use radiance::{RadianceSettings, RadianceSettingsError, RadianceState};
 pub mod render;
-use render::RenderState;
+use render::{RenderSettings, RenderState};
+
+/// Lowest fraction of the surface resolution the scene can be rendered at.
+pub const MIN_RENDER_SCALE: f32 = 1.0 / 16.0;
+
+/// Format of the offscreen target used when there is no window to present to.
+const HEADLESS_FORMAT: TextureFormat = TextureFormat::Rgba8Unorm;
+
+#[derive(Debug)]
+pub enum RenderTarget {
+    Surface(Surface),
+    Offscreen(TextureWithView),
+}
+
+impl RenderTarget {
+    fn create_offscreen(device: &Device, size: Vector2<u32>, format: TextureFormat) -> Self {
+        RenderTarget::Offscreen(TextureWithView::create_with_usage(
+            device,
+            size,
+            format,
+            TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub enum RenderError {
+    NoAdapter,
+    NoDevice(RequestDeviceError),
+    /// The adapter can't present to the window.
+    IncompatibleSurface,
+    InvalidRadianceSettings(RadianceSettingsError),
+    OutOfMemory,
+    /// A bitmap is larger than the textures the device allows.
+    BitmapTooLarge {
+        size: Vector2<u32>,
+        max: u32,
+    },
+}
+
+impl Display for RenderError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::NoAdapter => write!(f, "no suitable graphics adapter found"),
+            RenderError::NoDevice(err) => write!(f, "failed to open the graphics device: {}", err),
+            RenderError::IncompatibleSurface => {
+                write!(f, "the graphics adapter can't present to the window")
+            }
+            RenderError::InvalidRadianceSettings(err) => {
+                write!(f, "invalid radiance settings: {}", err)
+            }
+            RenderError::OutOfMemory => write!(f, "out of graphics memory"),
+            RenderError::BitmapTooLarge { size, max } => write!(
+                f,
+                "the bitmap is {}x{} texels, but the graphics device allows at most {}x{}",
+                size.x, size.y, max, max
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {}
+
+impl From<RadianceSettingsError> for RenderError {
+    fn from(err: RadianceSettingsError) -> Self {
+        RenderError::InvalidRadianceSettings(err)
+    }
+}
+
+/// How the adapter is picked and frames are presented.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceSettings {
+    pub power_preference: PowerPreference,
+    pub force_fallback_adapter: bool,
+    /// Ignored when rendering headless.
+    pub present_mode: PresentMode,
+}
+
+impl Default for DeviceSettings {
+    fn default() -> Self {
+        Self {
+            power_preference: PowerPreference::HighPerformance,
+            force_fallback_adapter: false,
+            present_mode: PresentMode::Fifo,
+        }
+    }
+}
 
-#[allow(dead_code)]
 #[derive(Debug, Clone, Copy)]
 pub struct IntermediateState<'a> {
-    instance: &'a Instance,
-    surface: &'a Surface,
-    adapter: &'a Adapter,
     device: &'a Device,
     queue: &'a Queue,
     config: &'a SurfaceConfiguration,
     size: Vector2<u32>,
     global_uniforms: &'a UniformData<GlobalUniforms>,
-    fullscreen_buffer: &'a Buffer,
     fullscreen_vert: &'a ShaderModule,
 }
 
-#[allow(dead_code)]
+impl IntermediateState<'_> {
+    fn surface_size(&self) -> Vector2<u32> {
+        vector![self.config.width, self.config.height]
+    }
+}
+
 #[derive(Debug)]
 pub struct State {
-    instance: Instance,
-    surface: Surface,
-    adapter: Adapter,
+    // Only kept alive for the surface and device.
+    _instance: Instance,
+    target: RenderTarget,
+    _adapter: Adapter,
     device: Device,
     queue: Queue,
     config: SurfaceConfiguration,
+    /// Resolution the scene is rendered at, in logical pixels of the surface.
     size: Vector2<u32>,
+    scale_factor: f64,
+    /// Set while the surface has no area, which pauses rendering.
+    minimized: bool,
+    camera: Camera,
     global_uniforms: UniformData<GlobalUniforms>,
     fullscreen_buffer: Buffer,
+    fullscreen_vert: ShaderModule,
     prerender_state: PrerenderState,
     radiance_state: RadianceState,
+    accumulate_state: AccumulateState,
+    exposure_state: ExposureState,
     render_state: RenderState,
 }
 
@@ -50,18 +161,61 @@ impl State {
         window: &Window,
         vertices: VertexList,
         radiance_settings: RadianceSettings,
-    ) -> Self {
+        device_settings: DeviceSettings,
+    ) -> Result<Self, RenderError> {
         let instance = Instance::new(Backends::all());
         let window_size = window.inner_size();
         let surface = unsafe { instance.create_surface(&window) };
+        Self::init_with_surface(
+            instance,
+            Some(surface),
+            // A minimized window can't configure its surface, so start at the smallest size.
+            vector![window_size.width.max(1), window_size.height.max(1)],
+            window.scale_factor(),
+            vertices,
+            radiance_settings,
+            device_settings,
+        )
+        .await
+    }
+
+    /// Creates a state that renders into an offscreen texture instead of a window.
+    pub async fn init_headless(
+        size: Vector2<u32>,
+        vertices: VertexList,
+        radiance_settings: RadianceSettings,
+        device_settings: DeviceSettings,
+    ) -> Result<Self, RenderError> {
+        let instance = Instance::new(Backends::all());
+        Self::init_with_surface(
+            instance,
+            None,
+            size,
+            1.0,
+            vertices,
+            radiance_settings,
+            device_settings,
+        )
+        .await
+    }
+
+    async fn init_with_surface(
+        instance: Instance,
+        surface: Option<Surface>,
+        surface_size: Vector2<u32>,
+        scale_factor: f64,
+        vertices: VertexList,
+        radiance_settings: RadianceSettings,
+        device_settings: DeviceSettings,
+    ) -> Result<Self, RenderError> {
         let adapter = instance
             .request_adapter(&RequestAdapterOptions {
-                power_preference: PowerPreference::HighPerformance,
-                force_fallback_adapter: false,
-                compatible_surface: Some(&surface),
+                power_preference: device_settings.power_preference,
+                force_fallback_adapter: device_settings.force_fallback_adapter,
+                compatible_surface: surface.as_ref(),
             })
             .await
-            .expect("No adapter");
+            .ok_or(RenderError::NoAdapter)?;
 
         let (device, queue) = adapter
             .request_device(
@@ -70,28 +224,44 @@ impl State {
                     features: Features::empty(),
                     limits: Limits {
                         max_bind_groups: 5,
+                        // Each light direction is a layer, so allow as many as the adapter can.
+                        max_texture_array_layers: adapter.limits().max_texture_array_layers,
                         ..Default::default()
                     },
                 },
                 None,
             )
             .await
-            .expect("No device");
+            .map_err(RenderError::NoDevice)?;
 
         let config = SurfaceConfiguration {
             usage: TextureUsages::RENDER_ATTACHMENT,
-            format: surface.get_preferred_format(&adapter).unwrap(),
-            width: window_size.width,
-            height: window_size.height,
-            present_mode: PresentMode::Fifo,
+            format: match &surface {
+                Some(surface) => surface
+                    .get_preferred_format(&adapter)
+                    .ok_or(RenderError::IncompatibleSurface)?,
+                None => HEADLESS_FORMAT,
+            },
+            width: surface_size.x,
+            height: surface_size.y,
+            present_mode: device_settings.present_mode,
         };
-        surface.configure(&device, &config);
-        let size = vector![config.width, config.height];
-
-        let global_uniforms =
-            UniformData::new(&device, false, ShaderStages::all(), GlobalUniforms {
-                window_size: vector![window_size.width as f32, window_size.height as f32],
-            });
+        let target = match surface {
+            Some(surface) => {
+                surface.configure(&device, &config);
+                RenderTarget::Surface(surface)
+            }
+            None => RenderTarget::create_offscreen(&device, surface_size, config.format),
+        };
+        let render_settings = RenderSettings::default();
+        let camera = Camera::new(Self::logical_size(surface_size, scale_factor));
+        let size = Self::render_size(surface_size, render_settings.render_scale);
+        let global_uniforms = UniformData::new(
+            &device,
+            true,
+            ShaderStages::all(),
+            Self::global_uniforms(size, surface_size, scale_factor, &camera),
+        );
 
         let fullscreen_buffer = device.create_buffer_init(&BufferInitDescriptor {
             label: None,
@@ -105,51 +275,433 @@ impl State {
             device.create_shader_module(&include_wgsl!("shaders/fullscreen.vert.wgsl"));
 
         let intermediate_state = IntermediateState {
-            instance: &instance,
-            surface: &surface,
-            adapter: &adapter,
             device: &device,
             queue: &queue,
             config: &config,
             size,
             global_uniforms: &global_uniforms,
-            fullscreen_buffer: &fullscreen_buffer,
             fullscreen_vert: &fullscreen_vert,
         };
 
         let prerender_state = PrerenderState::new(intermediate_state, vertices);
         let radiance_state =
-            RadianceState::new(intermediate_state, &prerender_state, radiance_settings);
-        let render_state = RenderState::new(intermediate_state, &prerender_state, &radiance_state);
+            RadianceState::new(intermediate_state, &prerender_state, radiance_settings)?;
+        let accumulate_state = AccumulateState::new(intermediate_state, &radiance_state);
+        let exposure_state =
+            ExposureState::new(intermediate_state, &radiance_state, &accumulate_state);
+        let render_state = RenderState::new(
+            intermediate_state,
+            &prerender_state,
+            &radiance_state,
+            &accumulate_state,
+            &exposure_state,
+            render_settings,
+        );
 
-        Self {
-            instance,
-            surface,
-            adapter,
+        Ok(Self {
+            _instance: instance,
+            target,
+            _adapter: adapter,
             device,
             queue,
             config,
             size,
+            scale_factor,
+            minimized: false,
+            camera,
             global_uniforms,
             fullscreen_buffer,
+            fullscreen_vert,
             prerender_state,
             radiance_state,
+            accumulate_state,
+            exposure_state,
             render_state,
+        })
+    }
+
+    fn split_mut(
+        &mut self,
+    ) -> (
+        IntermediateState<'_>,
+        &mut PrerenderState,
+        &mut RadianceState,
+        &mut AccumulateState,
+        &mut ExposureState,
+        &mut RenderState,
+    ) {
+        (
+            IntermediateState {
+                device: &self.device,
+                queue: &self.queue,
+                config: &self.config,
+                size: self.size,
+                global_uniforms: &self.global_uniforms,
+                fullscreen_vert: &self.fullscreen_vert,
+            },
+            &mut self.prerender_state,
+            &mut self.radiance_state,
+            &mut self.accumulate_state,
+            &mut self.exposure_state,
+            &mut self.render_state,
+        )
+    }
+
+    /// The scene is laid out in logical pixels but rendered at `render_scale` of the physical
+    /// surface size, so high-DPI surfaces get their full resolution unless scaled down.
+    fn render_size(surface_size: Vector2<u32>, render_scale: f32) -> Vector2<u32> {
+        let scale = render_scale.clamp(MIN_RENDER_SCALE, 1.0);
+        surface_size.map(|x| ((x as f32 * scale).round() as u32).max(1))
+    }
+
+    fn logical_size(surface_size: Vector2<u32>, scale_factor: f64) -> Vector2<f32> {
+        surface_size.cast() / scale_factor as f32
+    }
+
+    fn global_uniforms(
+        size: Vector2<u32>,
+        surface_size: Vector2<u32>,
+        scale_factor: f64,
+        camera: &Camera,
+    ) -> GlobalUniforms {
+        GlobalUniforms {
+            window_size: size.cast(),
+            surface_size: surface_size.cast(),
+            view_size: Self::logical_size(surface_size, scale_factor),
+            camera_position: camera.position,
+            camera_zoom: camera.zoom,
+            camera_rotation: camera.rotation,
         }
     }
-    pub fn render(&mut self) {
+
+    /// Size of the view in logical pixels, the unit of view coordinates of the camera.
+    pub fn view_size(&self) -> Vector2<f32> {
+        Self::logical_size(self.surface_size(), self.scale_factor)
+    }
+
+    pub fn surface_size(&self) -> Vector2<u32> {
+        vector![self.config.width, self.config.height]
+    }
+
+    pub fn minimized(&self) -> bool {
+        self.minimized
+    }
+
+    /// Resizes the surface to `new_size` physical pixels, shown at `scale_factor` physical
+    /// pixels per logical pixel.
+    pub fn resize(&mut self, new_size: PhysicalSize<u32>, scale_factor: f64) {
+        // Minimized windows report a zero size. Keep everything as it was until they come back.
+        self.minimized = new_size.width == 0 || new_size.height == 0;
+        if self.minimized {
+            return;
+        }
+
+        self.config.width = new_size.width;
+        self.config.height = new_size.height;
+        self.scale_factor = scale_factor;
+        match &self.target {
+            RenderTarget::Surface(surface) => surface.configure(&self.device, &self.config),
+            RenderTarget::Offscreen(_) => {
+                self.target = RenderTarget::create_offscreen(
+                    &self.device,
+                    self.surface_size(),
+                    self.config.format,
+                )
+            }
+        }
+        self.resize_render_textures();
+    }
+
+    /// Recreates everything sized by the render resolution, after the surface or the render
+    /// scale changed.
+    fn resize_render_textures(&mut self) {
+        let surface_size = self.surface_size();
+        self.size = Self::render_size(surface_size, self.render_state.settings().render_scale);
+        self.global_uniforms.update(
+            &self.queue,
+            Self::global_uniforms(self.size, surface_size, self.scale_factor, &self.camera),
+        );
+
+        let (st, prerender_state, radiance_state, accumulate_state, exposure_state, render_state) =
+            self.split_mut();
+        prerender_state.resize(st);
+        radiance_state.resize(st);
+        accumulate_state.resize(st, radiance_state);
+        exposure_state.resize(st, radiance_state, accumulate_state);
+        render_state.rebind(st, prerender_state, radiance_state);
+    }
+
+    pub fn camera(&self) -> Camera {
+        self.camera
+    }
+
+    /// Moves the camera. The accumulated history no longer lines up, so it starts over.
+    pub fn set_camera(&mut self, camera: Camera) {
+        let rotated = camera.rotation != self.camera.rotation;
+        self.camera = camera;
+        self.global_uniforms.update(
+            &self.queue,
+            Self::global_uniforms(self.size, self.surface_size(), self.scale_factor, &camera),
+        );
+
+        let (st, _, radiance_state, accumulate_state, _, _) = self.split_mut();
+        if rotated {
+            // The environment is fixed to the world, so it turns with the view.
+            radiance_state.write_radiance_uniforms(st);
+        }
+        accumulate_state.reset();
+    }
+
+    pub fn set_vertices(&mut self, vertices: VertexList) {
+        let (st, prerender_state, _, accumulate_state, _, _) = self.split_mut();
+        prerender_state.set_vertices(st, vertices);
+        accumulate_state.reset();
+    }
+
+    /// Replaces the texels drawn under the static geometry, or removes them. A bitmap too large
+    /// for the device is an error, and the previous one stays.
+    pub fn set_bitmap(&mut self, bitmap: Option<&Bitmap>) -> Result<(), RenderError> {
+        let (st, prerender_state, _, accumulate_state, _, _) = self.split_mut();
+        prerender_state.set_bitmap(st, bitmap)?;
+        accumulate_state.reset();
+        Ok(())
+    }
+
+    /// Replaces the objects drawn over the static geometry.
+    pub fn set_objects(&mut self, objects: Vec<Object>) {
+        let (st, prerender_state, _, accumulate_state, _, _) = self.split_mut();
+        prerender_state.set_objects(st, objects);
+        accumulate_state.reset();
+    }
+
+    /// Moves or recolors an object from the next frame on, without uploading its mesh again.
+    /// The accumulated history still shows it where it was, so it starts over on any change.
+    pub fn set_object_properties(&mut self, name: &str, properties: ObjectProperties) {
+        if let Some(current) = self.prerender_state.object_properties_mut(name) {
+            if *current != properties {
+                *current = properties;
+                self.accumulate_state.reset();
+            }
+        }
+    }
+
+    pub fn radiance_settings(&self) -> RadianceSettings {
+        self.radiance_state.settings()
+    }
+
+    /// Changes the radiance settings in place. Invalid settings are rejected and the current
+    /// ones are kept.
+    pub fn set_radiance_settings(
+        &mut self,
+        radiance_settings: RadianceSettings,
+    ) -> Result<(), RadianceSettingsError> {
+        let (st, prerender_state, radiance_state, accumulate_state, exposure_state, render_state) =
+            self.split_mut();
+        radiance_state.set_settings(st, radiance_settings)?;
+        accumulate_state.rebind(st, radiance_state);
+        exposure_state.rebind(st, radiance_state, accumulate_state);
+        render_state.rebind(st, prerender_state, radiance_state);
+        accumulate_state.reset();
+        Ok(())
+    }
+
+    pub fn render_settings(&self) -> RenderSettings {
+        self.render_state.settings()
+    }
+
+    pub fn set_render_settings(&mut self, render_settings: RenderSettings) {
+        let render_scale = self.render_state.settings().render_scale;
+        let (st, _, _, _, _, render_state) = self.split_mut();
+        render_state.set_settings(st, render_settings);
+        if render_settings.render_scale != render_scale {
+            self.resize_render_textures();
+        }
+    }
+
+    /// Sets the light entering from outside the window.
+    pub fn set_environment(&mut self, environment: Environment) {
+        let (st, _, radiance_state, accumulate_state, _, _) = self.split_mut();
+        radiance_state.set_environment(st, environment);
+        accumulate_state.reset();
+    }
+
+    /// Renders a frame, or skips it when the surface isn't ready. Lost and outdated surfaces are
+    /// reconfigured for the next frame; running out of memory is the only error.
+    pub fn render(&mut self) -> Result<(), RenderError> {
+        if self.minimized {
+            return Ok(());
+        }
+
+        // Acquire the frame first, so nothing is recorded for frames that get skipped.
+        let output = match &self.target {
+            RenderTarget::Surface(surface) => match surface.get_current_texture() {
+                Ok(output) => Some(output),
+                Err(SurfaceError::Lost | SurfaceError::Outdated) => {
+                    surface.configure(&self.device, &self.config);
+                    return Ok(());
+                }
+                Err(SurfaceError::Timeout) => return Ok(()),
+                Err(SurfaceError::OutOfMemory) => return Err(RenderError::OutOfMemory),
+            },
+            RenderTarget::Offscreen(_) => None,
+        };
+
         let mut encoder = self
             .device
             .create_command_encoder(&CommandEncoderDescriptor { label: None });
 
         PrerenderState::render(self, &mut encoder);
         RadianceState::render(self, &mut encoder);
+        AccumulateState::render(self, &mut encoder);
+        ExposureState::render(self, &mut encoder);
+
+        match output {
+            Some(output) => {
+                let view = output
+                    .texture
+                    .create_view(&TextureViewDescriptor::default());
 
-        let output = self.surface.get_current_texture().unwrap();
+                RenderState::render(self, &mut encoder, &view);
 
-        RenderState::render(self, &mut encoder, &output);
+                self.queue.submit(std::iter::once(encoder.finish()));
+                output.present();
+            }
+            None => {
+                if let RenderTarget::Offscreen(texture) = &self.target {
+                    RenderState::render(self, &mut encoder, &texture.1);
+                }
+
+                self.queue.submit(std::iter::once(encoder.finish()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads back the lit scene of the last frame in linear color, before temporal accumulation
+    /// and output encoding.
+    pub fn read_total_radiance(&self) -> ImageBuffer<LinSrgba> {
+        read_texture(
+            &self.device,
+            &self.queue,
+            &self.radiance_state.radiance_textures.total_radiance.0,
+            self.size,
+        )
+    }
+
+    pub fn read_prerender_textures(&self) -> PrerenderImages {
+        let textures = &self.prerender_state.prerender_textures;
+        PrerenderImages {
+            albedo_lin: read_texture(&self.device, &self.queue, &textures.albedo_lin.0, self.size),
+            radiance_lin: read_texture(
+                &self.device,
+                &self.queue,
+                &textures.radiance_lin.0,
+                self.size,
+            ),
+            normal: read_texture(&self.device, &self.queue, &textures.normal.0, self.size),
+        }
+    }
+
+    /// Runs the CPU reference solver on the current prerender output, for comparison with
+    /// `read_total_radiance`.
+    pub fn compute_reference_radiance(&self) -> ImageBuffer<LinSrgba> {
+        let settings = self.radiance_state.settings();
+        let (directional_uniforms, workgroups) = RadianceState::compute_radiance_uniforms(
+            self.size,
+            settings.light_directions,
+            self.radiance_state.jitter(),
+            self.radiance_state.environment(),
+            self.camera.rotation,
+        );
+        reference::solve(
+            &self.read_prerender_textures(),
+            settings,
+            &directional_uniforms,
+            &workgroups,
+        )
+    }
+
+    /// Reads back the radiance of the last frame that the render pass shows, after temporal
+    /// accumulation.
+    pub fn read_radiance(&self) -> ImageBuffer<LinSrgba> {
+        read_texture(
+            &self.device,
+            &self.queue,
+            &self.accumulate_state.output(&self.radiance_state).0,
+            self.size,
+        )
+    }
+
+    /// Reads back the last frame as displayed. Surface textures can't be copied from, so
+    /// when rendering to a window the render pass is repeated into a texture of the same format.
+    pub fn read_output(&self) -> ImageBuffer<Srgba<u8>> {
+        let mut image: ImageBuffer<Srgba<u8>> = match &self.target {
+            RenderTarget::Surface(_) => {
+                let texture = TextureWithView::create_with_usage(
+                    &self.device,
+                    self.surface_size(),
+                    self.config.format,
+                    TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+                );
+                let mut encoder = self
+                    .device
+                    .create_command_encoder(&CommandEncoderDescriptor { label: None });
+                RenderState::render(self, &mut encoder, &texture.1);
+                self.queue.submit(std::iter::once(encoder.finish()));
+                read_texture(&self.device, &self.queue, &texture.0, self.surface_size())
+            }
+            RenderTarget::Offscreen(texture) => {
+                read_texture(&self.device, &self.queue, &texture.0, self.surface_size())
+            }
+        };
+
+        if matches!(
+            self.config.format,
+            TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb
+        ) {
+            for pixel in &mut image.pixels {
+                let color = &mut pixel.color;
+                std::mem::swap(&mut color.red, &mut color.blue);
+            }
+        }
+        image
+    }
+
+    /// Writes the radiance to `<path>.pfm` and the displayed frame to `<path>.png`.
+    pub fn save_screenshot(&self, path: impl AsRef<Path>) -> Result<(), ScreenshotError> {
+        let path = path.as_ref();
+        screenshot::write_pfm(path.with_extension("pfm"), &self.read_radiance())?;
+        screenshot::write_png(path.with_extension("png"), &self.read_output())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_size_follows_the_physical_surface() {
+        assert_eq!(State::render_size(vector![2560, 1440], 1.0), vector![
+            2560, 1440
+        ]);
+        assert_eq!(State::render_size(vector![2560, 1440], 0.5), vector![
+            1280, 720
+        ]);
+        assert_eq!(State::render_size(vector![801, 601], 0.5), vector![
+            401, 301
+        ]);
+    }
 
-        self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+    #[test]
+    fn render_size_is_clamped() {
+        assert_eq!(State::render_size(vector![800, 600], 2.0), vector![
+            800, 600
+        ]);
+        assert_eq!(State::render_size(vector![800, 600], 0.0), vector![50, 38]);
+        // Never zero, so the textures stay valid.
+        assert_eq!(
+            State::render_size(vector![3, 1], MIN_RENDER_SCALE),
+            vector![1, 1]
+        );
     }
 }
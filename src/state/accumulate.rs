@@ -0,0 +1,308 @@
+use bytemuck::{Pod, Zeroable};
+use nalgebra::Vector2;
+use std::mem::size_of;
+use wgpu::*;
+
+use super::radiance::RadianceState;
+use super::{IntermediateState, State};
+use crate::texture::TextureWithView;
+use crate::uniform::UniformData;
+
+#[derive(Debug)]
+pub struct AccumulateTextures {
+    pub history: TextureWithView,
+    pub previous_history: TextureWithView,
+}
+
+impl AccumulateTextures {
+    pub fn new(device: &Device, size: Vector2<u32>) -> Self {
+        Self {
+            history: TextureWithView::create_with_usage(
+                device,
+                size,
+                TextureFormat::Rgba32Float,
+                TextureUsages::TEXTURE_BINDING
+                    | TextureUsages::RENDER_ATTACHMENT
+                    | TextureUsages::COPY_SRC,
+            ),
+            previous_history: TextureWithView::create_with_usage(
+                device,
+                size,
+                TextureFormat::Rgba32Float,
+                TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            ),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+pub struct AccumulateUniforms {
+    weight: f32,
+}
+
+/// Blends each frame's `total_radiance` into a history texture when
+/// `RadianceSettings::temporal_accumulate` is set.
+#[derive(Debug)]
+pub struct AccumulateState {
+    accumulated_frames: u32,
+    pub accumulate_textures: AccumulateTextures,
+    accumulate_uniforms: UniformData<AccumulateUniforms>,
+    accumulate_bind_group_layout: BindGroupLayout,
+    accumulate_bind_group: BindGroup,
+    accumulate_pipeline: RenderPipeline,
+    pub output_bind_group_layout: BindGroupLayout,
+    pub output_bind_group: BindGroup,
+}
+
+fn texture_entry(binding: u32) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding,
+        visibility: ShaderStages::FRAGMENT,
+        ty: BindingType::Texture {
+            sample_type: TextureSampleType::Float { filterable: false },
+            view_dimension: TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    }
+}
+
+impl AccumulateState {
+    pub fn new(st: IntermediateState, radiance_state: &RadianceState) -> Self {
+        let accumulate_textures = AccumulateTextures::new(st.device, st.size);
+
+        let accumulate_uniforms = UniformData::new(
+            st.device,
+            true,
+            ShaderStages::FRAGMENT,
+            AccumulateUniforms { weight: 1.0 },
+        );
+
+        let accumulate_bind_group_layout =
+            st.device
+                .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                    label: None,
+                    entries: &[texture_entry(0), texture_entry(1)],
+                });
+        let accumulate_bind_group = Self::create_accumulate_bind_group(
+            st.device,
+            &accumulate_bind_group_layout,
+            radiance_state,
+            &accumulate_textures,
+        );
+
+        let output_bind_group_layout =
+            st.device
+                .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                    label: None,
+                    entries: &[texture_entry(0)],
+                });
+        let output_bind_group = Self::create_output_bind_group(
+            st.device,
+            &output_bind_group_layout,
+            radiance_state,
+            &accumulate_textures,
+        );
+
+        let accumulate_pipeline_layout =
+            st.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[
+                    &st.global_uniforms.bind_group_layout,
+                    &accumulate_uniforms.bind_group_layout,
+                    &accumulate_bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            });
+
+        let accumulate_pipeline = st.device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&accumulate_pipeline_layout),
+            vertex: VertexState {
+                module: st.fullscreen_vert,
+                entry_point: "main",
+                buffers: &[VertexBufferLayout {
+                    array_stride: size_of::<Vector2<f32>>() as BufferAddress,
+                    step_mode: VertexStepMode::Vertex,
+                    attributes: &vertex_attr_array![0 => Float32x2],
+                }],
+            },
+            fragment: Some(FragmentState {
+                module: &st.device.create_shader_module(&include_wgsl!(
+                    "../shaders/radiance_accumulate.frag.wgsl"
+                )),
+                entry_point: "main",
+                targets: &[ColorTargetState {
+                    format: TextureFormat::Rgba32Float,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                }],
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: FrontFace::Ccw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+
+        Self {
+            accumulated_frames: 0,
+            accumulate_textures,
+            accumulate_uniforms,
+            accumulate_bind_group_layout,
+            accumulate_bind_group,
+            accumulate_pipeline,
+            output_bind_group_layout,
+            output_bind_group,
+        }
+    }
+
+    fn create_accumulate_bind_group(
+        device: &Device,
+        layout: &BindGroupLayout,
+        radiance_state: &RadianceState,
+        accumulate_textures: &AccumulateTextures,
+    ) -> BindGroup {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(
+                        &radiance_state.radiance_textures.total_radiance.1,
+                    ),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&accumulate_textures.previous_history.1),
+                },
+            ],
+        })
+    }
+
+    fn create_output_bind_group(
+        device: &Device,
+        layout: &BindGroupLayout,
+        radiance_state: &RadianceState,
+        accumulate_textures: &AccumulateTextures,
+    ) -> BindGroup {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(
+                    &Self::select_output(radiance_state, accumulate_textures).1,
+                ),
+            }],
+        })
+    }
+
+    fn select_output<'a>(
+        radiance_state: &'a RadianceState,
+        accumulate_textures: &'a AccumulateTextures,
+    ) -> &'a TextureWithView {
+        if radiance_state.settings().temporal_accumulate {
+            &accumulate_textures.history
+        } else {
+            &radiance_state.radiance_textures.total_radiance
+        }
+    }
+
+    /// The final radiance of the frame: the history when accumulating, otherwise `total_radiance`.
+    pub fn output<'a>(&'a self, radiance_state: &'a RadianceState) -> &'a TextureWithView {
+        Self::select_output(radiance_state, &self.accumulate_textures)
+    }
+
+    /// Recreates the textures and everything bound to the radiance textures, which also
+    /// restarts the accumulation.
+    pub fn resize(&mut self, st: IntermediateState, radiance_state: &RadianceState) {
+        self.accumulate_textures = AccumulateTextures::new(st.device, st.size);
+        self.rebind(st, radiance_state);
+        self.reset();
+    }
+
+    /// Recreates the bind groups after `radiance_state` reallocated its textures or toggled
+    /// `temporal_accumulate`.
+    pub fn rebind(&mut self, st: IntermediateState, radiance_state: &RadianceState) {
+        self.accumulate_bind_group = Self::create_accumulate_bind_group(
+            st.device,
+            &self.accumulate_bind_group_layout,
+            radiance_state,
+            &self.accumulate_textures,
+        );
+        self.output_bind_group = Self::create_output_bind_group(
+            st.device,
+            &self.output_bind_group_layout,
+            radiance_state,
+            &self.accumulate_textures,
+        );
+    }
+
+    /// Discards the history, so the next frame starts a new average.
+    pub fn reset(&mut self) {
+        self.accumulated_frames = 0;
+    }
+
+    pub fn render(st: &mut State, encoder: &mut CommandEncoder) {
+        let settings = st.radiance_state.settings();
+        if !settings.temporal_accumulate {
+            return;
+        }
+
+        let state = &mut st.accumulate_state;
+        // Progressive average of all frames so far, until the blend factor takes over and it
+        // becomes an exponential moving average. The first frame after a reset replaces the history.
+        let weight = (1.0 / (state.accumulated_frames + 1) as f32).max(settings.temporal_blend);
+        state
+            .accumulate_uniforms
+            .update(&st.queue, AccumulateUniforms { weight });
+        state.accumulated_frames = state.accumulated_frames.saturating_add(1);
+
+        encoder.copy_texture_to_texture(
+            ImageCopyTexture {
+                texture: &state.accumulate_textures.history.0,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            ImageCopyTexture {
+                texture: &state.accumulate_textures.previous_history.0,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            Extent3d {
+                width: st.size.x,
+                height: st.size.y,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        let mut accumulate_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: None,
+            color_attachments: &[state.accumulate_textures.history.attachment()],
+            depth_stencil_attachment: None,
+        });
+
+        accumulate_pass.set_pipeline(&state.accumulate_pipeline);
+        accumulate_pass.set_vertex_buffer(0, st.fullscreen_buffer.slice(..));
+        accumulate_pass.set_bind_group(0, &st.global_uniforms.bind_group, &[]);
+        accumulate_pass.set_bind_group(1, &state.accumulate_uniforms.bind_group, &[]);
+        accumulate_pass.set_bind_group(2, &state.accumulate_bind_group, &[]);
+        accumulate_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,311 @@
+use bytemuck::{bytes_of, Pod, Zeroable};
+use nalgebra::Vector2;
+use std::mem::size_of;
+use std::time::Instant;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+use super::accumulate::AccumulateState;
+use super::radiance::RadianceState;
+use super::{IntermediateState, State};
+use crate::uniform::UniformData;
+
+/// Must match `exposure_histogram.glsl`.
+const HISTOGRAM_BINS: u32 = 256;
+const HISTOGRAM_TILE_SIZE: u32 = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutoExposureSettings {
+    /// Range the automatic exposure is clamped to, in stops.
+    pub min_ev: f32,
+    pub max_ev: f32,
+    /// How quickly the exposure follows changes in brightness, per second. Zero freezes it.
+    pub adaptation_speed: f32,
+}
+
+impl Default for AutoExposureSettings {
+    fn default() -> Self {
+        Self {
+            min_ev: -8.0,
+            max_ev: 8.0,
+            adaptation_speed: 2.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+pub struct ExposureUniforms {
+    delta_time: f32,
+    adaptation_speed: f32,
+    min_ev: f32,
+    max_ev: f32,
+    reset_adaptation: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+pub struct Exposure {
+    pub average_log_luminance: f32,
+    pub exposure_ev: f32,
+}
+
+/// Adapts the exposure to the average luminance of the final radiance, from a histogram
+/// built on the GPU.
+#[derive(Debug)]
+pub struct ExposureState {
+    last_update: Option<Instant>,
+    exposure_uniforms: UniformData<ExposureUniforms>,
+    tile_histograms_buffer: Buffer,
+    exposure_buffer: Buffer,
+    exposure_bind_group_layout: BindGroupLayout,
+    exposure_bind_group: BindGroup,
+    histogram_pipeline: ComputePipeline,
+    average_pipeline: ComputePipeline,
+    pub output_bind_group_layout: BindGroupLayout,
+    pub output_bind_group: BindGroup,
+}
+
+fn buffer_entry(binding: u32, visibility: ShaderStages, read_only: bool) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: BindingType::Buffer {
+            ty: BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+impl ExposureState {
+    pub fn new(
+        st: IntermediateState,
+        radiance_state: &RadianceState,
+        accumulate_state: &AccumulateState,
+    ) -> Self {
+        let exposure_uniforms =
+            UniformData::new(st.device, true, ShaderStages::COMPUTE, ExposureUniforms {
+                delta_time: 0.0,
+                adaptation_speed: 0.0,
+                min_ev: 0.0,
+                max_ev: 0.0,
+                reset_adaptation: 1,
+            });
+
+        let tile_histograms_buffer = Self::create_tile_histograms_buffer(st);
+        let exposure_buffer = st.device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: bytes_of(&Exposure {
+                average_log_luminance: 0.18_f32.log2(),
+                exposure_ev: 0.0,
+            }),
+            usage: BufferUsages::STORAGE,
+        });
+
+        let exposure_bind_group_layout =
+            st.device
+                .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                    label: None,
+                    entries: &[
+                        BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: ShaderStages::COMPUTE,
+                            ty: BindingType::Texture {
+                                sample_type: TextureSampleType::Float { filterable: false },
+                                view_dimension: TextureViewDimension::D2,
+                                multisampled: false,
+                            },
+                            count: None,
+                        },
+                        buffer_entry(1, ShaderStages::COMPUTE, false),
+                        buffer_entry(2, ShaderStages::COMPUTE, false),
+                    ],
+                });
+        let exposure_bind_group = Self::create_exposure_bind_group(
+            st.device,
+            &exposure_bind_group_layout,
+            radiance_state,
+            accumulate_state,
+            &tile_histograms_buffer,
+            &exposure_buffer,
+        );
+
+        let output_bind_group_layout =
+            st.device
+                .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                    label: None,
+                    entries: &[buffer_entry(0, ShaderStages::FRAGMENT, true)],
+                });
+        let output_bind_group = st.device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &output_bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: exposure_buffer.as_entire_binding(),
+            }],
+        });
+
+        let exposure_pipeline_layout =
+            st.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[
+                    &st.global_uniforms.bind_group_layout,
+                    &exposure_uniforms.bind_group_layout,
+                    &exposure_bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            });
+
+        let histogram_pipeline = st
+            .device
+            .create_compute_pipeline(&ComputePipelineDescriptor {
+                label: None,
+                layout: Some(&exposure_pipeline_layout),
+                module: &st.device.create_shader_module(&include_wgsl!(
+                    "../shaders/exposure_histogram.comp.wgsl"
+                )),
+                entry_point: "main",
+            });
+
+        let average_pipeline = st
+            .device
+            .create_compute_pipeline(&ComputePipelineDescriptor {
+                label: None,
+                layout: Some(&exposure_pipeline_layout),
+                module: &st
+                    .device
+                    .create_shader_module(&include_wgsl!("../shaders/exposure_average.comp.wgsl")),
+                entry_point: "main",
+            });
+
+        Self {
+            last_update: None,
+            exposure_uniforms,
+            tile_histograms_buffer,
+            exposure_buffer,
+            exposure_bind_group_layout,
+            exposure_bind_group,
+            histogram_pipeline,
+            average_pipeline,
+            output_bind_group_layout,
+            output_bind_group,
+        }
+    }
+
+    fn tiles(size: Vector2<u32>) -> (u32, u32) {
+        (
+            size.x.div_ceil(HISTOGRAM_TILE_SIZE),
+            size.y.div_ceil(HISTOGRAM_TILE_SIZE),
+        )
+    }
+
+    /// One histogram per tile, summed up by the average pass.
+    fn create_tile_histograms_buffer(st: IntermediateState) -> Buffer {
+        let (tiles_x, tiles_y) = Self::tiles(st.size);
+        st.device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: (tiles_x * tiles_y * HISTOGRAM_BINS) as BufferAddress
+                * size_of::<u32>() as BufferAddress,
+            usage: BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_exposure_bind_group(
+        device: &Device,
+        layout: &BindGroupLayout,
+        radiance_state: &RadianceState,
+        accumulate_state: &AccumulateState,
+        tile_histograms_buffer: &Buffer,
+        exposure_buffer: &Buffer,
+    ) -> BindGroup {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(
+                        &accumulate_state.output(radiance_state).1,
+                    ),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: tile_histograms_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: exposure_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    /// Recreates the tile histograms and rebinds the radiance the histogram is built from.
+    pub fn resize(
+        &mut self,
+        st: IntermediateState,
+        radiance_state: &RadianceState,
+        accumulate_state: &AccumulateState,
+    ) {
+        self.tile_histograms_buffer = Self::create_tile_histograms_buffer(st);
+        self.rebind(st, radiance_state, accumulate_state);
+    }
+
+    /// Recreates the bind group after the output of `accumulate_state` changed.
+    pub fn rebind(
+        &mut self,
+        st: IntermediateState,
+        radiance_state: &RadianceState,
+        accumulate_state: &AccumulateState,
+    ) {
+        self.exposure_bind_group = Self::create_exposure_bind_group(
+            st.device,
+            &self.exposure_bind_group_layout,
+            radiance_state,
+            accumulate_state,
+            &self.tile_histograms_buffer,
+            &self.exposure_buffer,
+        );
+    }
+
+    pub fn render(st: &mut State, encoder: &mut CommandEncoder) {
+        let settings = match st.render_state.settings().auto_exposure {
+            Some(settings) => settings,
+            None => {
+                // Adapt from scratch once it is turned back on.
+                st.exposure_state.last_update = None;
+                return;
+            }
+        };
+
+        let state = &mut st.exposure_state;
+        let now = Instant::now();
+        let uniforms = ExposureUniforms {
+            delta_time: state
+                .last_update
+                .map_or(0.0, |last_update| (now - last_update).as_secs_f32()),
+            adaptation_speed: settings.adaptation_speed,
+            min_ev: settings.min_ev,
+            max_ev: settings.max_ev.max(settings.min_ev),
+            reset_adaptation: state.last_update.is_none() as u32,
+        };
+        state.exposure_uniforms.update(&st.queue, uniforms);
+        state.last_update = Some(now);
+
+        let (tiles_x, tiles_y) = Self::tiles(st.size);
+        let state = &st.exposure_state;
+        let mut exposure_pass = encoder.begin_compute_pass(&ComputePassDescriptor { label: None });
+        exposure_pass.set_bind_group(0, &st.global_uniforms.bind_group, &[]);
+        exposure_pass.set_bind_group(1, &state.exposure_uniforms.bind_group, &[]);
+        exposure_pass.set_bind_group(2, &state.exposure_bind_group, &[]);
+
+        exposure_pass.set_pipeline(&state.histogram_pipeline);
+        exposure_pass.dispatch(tiles_x, tiles_y, 1);
+
+        exposure_pass.set_pipeline(&state.average_pipeline);
+        exposure_pass.dispatch(1, 1, 1);
+    }
+}
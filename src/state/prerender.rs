@@ -1,8 +1,14 @@
+use bytemuck::{Pod, Zeroable};
 use nalgebra::Vector2;
+use palette::{Alpha, LinSrgba};
+use std::mem::size_of;
+use std::num::{NonZeroU32, NonZeroU64};
 use wgpu::util::{BufferInitDescriptor, DeviceExt};
 use wgpu::*;
 
-use super::{IntermediateState, State};
+use super::{IntermediateState, RenderError, State};
+use crate::bitmap::Bitmap;
+use crate::object::{Object, ObjectProperties, ObjectUniforms};
 use crate::texture::TextureWithView;
 use crate::vertex::{Vertex, VertexList};
 
@@ -36,20 +42,151 @@ impl PrerenderTextures {
             ),
         }
     }
-    pub fn attachments(&self) -> [RenderPassColorAttachment; 3] {
-        [
-            self.albedo_lin.attachment(),
-            self.radiance_lin.attachment(),
-            self.normal.attachment(),
-        ]
+    /// Attachments that are cleared first, or keep what is there if `clear` is false.
+    pub fn attachments(&self, clear: bool) -> [RenderPassColorAttachment; 3] {
+        [&self.albedo_lin, &self.radiance_lin, &self.normal].map(|texture| {
+            let mut attachment = texture.attachment();
+            if !clear {
+                attachment.ops.load = LoadOp::Load;
+            }
+            attachment
+        })
     }
 }
 
+/// Must match `BitmapUniforms` in `bitmap.frag`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct BitmapUniforms {
+    origin: Vector2<f32>,
+    texel_size: f32,
+    _padding: f32,
+}
+
+/// A `Bitmap` on the GPU.
+#[derive(Debug)]
+struct BitmapTextures {
+    bind_group: BindGroup,
+}
+
+impl BitmapTextures {
+    fn new(
+        st: IntermediateState,
+        layout: &BindGroupLayout,
+        bitmap: &Bitmap,
+    ) -> Result<Self, RenderError> {
+        check_texture_size(bitmap.size, st.device.limits().max_texture_dimension_2d)?;
+        let uniforms = st.device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::bytes_of(&BitmapUniforms {
+                origin: bitmap.origin,
+                texel_size: bitmap.texel_size,
+                _padding: 0.0,
+            }),
+            usage: BufferUsages::UNIFORM,
+        });
+        // The radiance shares its alpha with the albedo, like in the prerender textures.
+        let radiance: Vec<LinSrgba> = bitmap
+            .emission
+            .iter()
+            .zip(&bitmap.albedo)
+            .map(|(&color, albedo)| Alpha {
+                color,
+                alpha: albedo.alpha,
+            })
+            .collect();
+        let upload = |format, bytes: &[u8]| {
+            let texture = TextureWithView::create_with_usage(
+                st.device,
+                bitmap.size,
+                format,
+                TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            );
+            st.queue.write_texture(
+                texture.0.as_image_copy(),
+                bytes,
+                ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(bytes.len() as u32 / bitmap.size.y),
+                    rows_per_image: None,
+                },
+                Extent3d {
+                    width: bitmap.size.x,
+                    height: bitmap.size.y,
+                    depth_or_array_layers: 1,
+                },
+            );
+            texture
+        };
+        let albedo = upload(
+            TextureFormat::Rgba32Float,
+            bytemuck::cast_slice(&bitmap.albedo),
+        );
+        let radiance = upload(TextureFormat::Rgba32Float, bytemuck::cast_slice(&radiance));
+        let normal = upload(
+            TextureFormat::Rg32Float,
+            bytemuck::cast_slice(&bitmap.normal),
+        );
+        let bind_group = st.device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: uniforms.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&albedo.1),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::TextureView(&radiance.1),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: BindingResource::TextureView(&normal.1),
+                },
+            ],
+        });
+        Ok(Self { bind_group })
+    }
+}
+
+/// Textures larger than `max` on either side make wgpu panic, so they are an error instead.
+fn check_texture_size(size: Vector2<u32>, max: u32) -> Result<(), RenderError> {
+    if size.x > max || size.y > max {
+        return Err(RenderError::BitmapTooLarge { size, max });
+    }
+    Ok(())
+}
+
 #[derive(Debug)]
 pub struct PrerenderState {
     vertices: VertexList,
+    objects: Vec<Object>,
+    /// The static vertices, followed by the mesh of every object.
     vertex_buffer: Buffer,
+    vertex_buffer_capacity: BufferAddress,
+    /// The indices of every mesh in the same order, each relative to the first vertex of its
+    /// mesh.
+    index_buffer: Buffer,
+    index_buffer_capacity: BufferAddress,
+    /// The properties of the static geometry followed by those of every object, each
+    /// `object_stride` bytes apart and bound with a dynamic offset for its draw.
+    object_buffer: Buffer,
+    object_buffer_capacity: usize,
+    object_stride: BufferAddress,
+    object_bind_group_layout: BindGroupLayout,
+    object_bind_group: BindGroup,
+    /// Texels drawn before the triangles, which then go over them.
+    bitmap: Option<BitmapTextures>,
+    bitmap_bind_group_layout: BindGroupLayout,
+    bitmap_pipeline: RenderPipeline,
     pub prerender_textures: PrerenderTextures,
+    /// The scene again at the surface resolution, to guide upsampling. Only there when the
+    /// render resolution is lower.
+    pub guide_textures: Option<PrerenderTextures>,
     prerender_pipeline: RenderPipeline,
     pub prerender_output_bind_group_layout: BindGroupLayout,
     pub prerender_output_bind_group: BindGroup,
@@ -57,21 +194,78 @@ pub struct PrerenderState {
 
 impl PrerenderState {
     pub fn new(st: IntermediateState, vertices: VertexList) -> Self {
-        let vertex_buffer = st.device.create_buffer_init(&BufferInitDescriptor {
-            label: None,
-            contents: vertices.to_bytes(),
-            usage: BufferUsages::VERTEX,
-        });
+        let vertex_buffer =
+            Self::create_mesh_buffer(st.device, vertices.vertex_bytes(), BufferUsages::VERTEX);
+        let vertex_buffer_capacity = vertices.vertex_bytes().len() as BufferAddress;
+        let index_buffer =
+            Self::create_mesh_buffer(st.device, vertices.index_bytes(), BufferUsages::INDEX);
+        let index_buffer_capacity = vertices.index_bytes().len() as BufferAddress;
+
+        let object_buffer_capacity = 1;
+        // Dynamic offsets must be aligned, which usually leaves room between the objects.
+        let object_stride = (size_of::<ObjectUniforms>() as BufferAddress).next_multiple_of(
+            st.device.limits().min_uniform_buffer_offset_alignment as BufferAddress,
+        );
+        let object_buffer =
+            Self::create_object_buffer(st.device, object_buffer_capacity, object_stride);
+        let object_bind_group_layout =
+            st.device
+                .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                    label: None,
+                    entries: &[BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::VERTEX,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Uniform,
+                            has_dynamic_offset: true,
+                            min_binding_size: NonZeroU64::new(size_of::<ObjectUniforms>() as u64),
+                        },
+                        count: None,
+                    }],
+                });
+        let object_bind_group =
+            Self::create_object_bind_group(st.device, &object_bind_group_layout, &object_buffer);
 
         let prerender_textures = PrerenderTextures::new(st.device, st.size);
+        let guide_textures = Self::create_guide_textures(st);
 
         let prerender_pipeline_layout =
             st.device.create_pipeline_layout(&PipelineLayoutDescriptor {
                 label: None,
-                bind_group_layouts: &[&st.global_uniforms.bind_group_layout],
+                bind_group_layouts: &[
+                    &st.global_uniforms.bind_group_layout,
+                    &object_bind_group_layout,
+                ],
                 push_constant_ranges: &[],
             });
 
+        let targets = [
+            ColorTargetState {
+                format: TextureFormat::Rgba32Float,
+                blend: None,
+                write_mask: ColorWrites::ALL,
+            },
+            ColorTargetState {
+                format: TextureFormat::Rgba32Float,
+                blend: None,
+                write_mask: ColorWrites::ALL,
+            },
+            ColorTargetState {
+                format: TextureFormat::Rg32Float,
+                blend: None,
+                write_mask: ColorWrites::ALL,
+            },
+        ];
+        let primitive = PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: FrontFace::Ccw,
+            cull_mode: None,
+            unclipped_depth: false,
+            polygon_mode: PolygonMode::Fill,
+            conservative: false,
+        };
+
         let prerender_pipeline = st.device.create_render_pipeline(&RenderPipelineDescriptor {
             label: None,
             layout: Some(&prerender_pipeline_layout),
@@ -87,33 +281,81 @@ impl PrerenderState {
                     .device
                     .create_shader_module(&include_wgsl!("../shaders/prerender.frag.wgsl")),
                 entry_point: "main",
-                targets: &[
-                    ColorTargetState {
-                        format: TextureFormat::Rgba32Float,
-                        blend: None,
-                        write_mask: ColorWrites::ALL,
-                    },
-                    ColorTargetState {
-                        format: TextureFormat::Rgba32Float,
-                        blend: None,
-                        write_mask: ColorWrites::ALL,
-                    },
-                    ColorTargetState {
-                        format: TextureFormat::Rg32Float,
-                        blend: None,
-                        write_mask: ColorWrites::ALL,
-                    },
-                ],
+                targets: &targets,
             }),
-            primitive: PrimitiveState {
-                topology: PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: FrontFace::Ccw,
-                cull_mode: None,
-                unclipped_depth: false,
-                polygon_mode: PolygonMode::Fill,
-                conservative: false,
+            primitive,
+            depth_stencil: None,
+            multisample: MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+
+        let bitmap_texture_entry = |binding| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: false },
+                view_dimension: TextureViewDimension::D2,
+                multisampled: false,
             },
+            count: None,
+        };
+        let bitmap_bind_group_layout =
+            st.device
+                .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                    label: None,
+                    entries: &[
+                        BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: ShaderStages::FRAGMENT,
+                            ty: BindingType::Buffer {
+                                ty: BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            count: None,
+                        },
+                        bitmap_texture_entry(1),
+                        bitmap_texture_entry(2),
+                        bitmap_texture_entry(3),
+                    ],
+                });
+
+        let bitmap_pipeline_layout = st.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[
+                &st.global_uniforms.bind_group_layout,
+                &bitmap_bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        // Covers the whole target, so every texel of the scene is looked up in the bitmap.
+        let bitmap_pipeline = st.device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&bitmap_pipeline_layout),
+            vertex: VertexState {
+                module: &st
+                    .device
+                    .create_shader_module(&include_wgsl!("../shaders/bitmap.vert.wgsl")),
+                entry_point: "main",
+                buffers: &[VertexBufferLayout {
+                    array_stride: size_of::<Vector2<f32>>() as BufferAddress,
+                    step_mode: VertexStepMode::Vertex,
+                    attributes: &vertex_attr_array![0 => Float32x2],
+                }],
+            },
+            fragment: Some(FragmentState {
+                module: &st
+                    .device
+                    .create_shader_module(&include_wgsl!("../shaders/bitmap.frag.wgsl")),
+                entry_point: "main",
+                targets: &targets,
+            }),
+            primitive,
             depth_stencil: None,
             multisample: MultisampleState {
                 count: 1,
@@ -161,9 +403,174 @@ impl PrerenderState {
                     ],
                 });
 
-        let prerender_output_bind_group = st.device.create_bind_group(&BindGroupDescriptor {
+        let prerender_output_bind_group = Self::create_output_bind_group(
+            st.device,
+            &prerender_output_bind_group_layout,
+            &prerender_textures,
+        );
+
+        Self {
+            vertices,
+            objects: Vec::new(),
+            vertex_buffer,
+            vertex_buffer_capacity,
+            index_buffer,
+            index_buffer_capacity,
+            object_buffer,
+            object_buffer_capacity,
+            object_stride,
+            object_bind_group_layout,
+            object_bind_group,
+            bitmap: None,
+            bitmap_bind_group_layout,
+            bitmap_pipeline,
+            prerender_textures,
+            guide_textures,
+            prerender_pipeline,
+            prerender_output_bind_group_layout,
+            prerender_output_bind_group,
+        }
+    }
+
+    fn create_guide_textures(st: IntermediateState) -> Option<PrerenderTextures> {
+        let surface_size = st.surface_size();
+        (surface_size != st.size).then(|| PrerenderTextures::new(st.device, surface_size))
+    }
+
+    fn create_mesh_buffer(device: &Device, contents: &[u8], usage: BufferUsages) -> Buffer {
+        device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents,
+            usage: usage | BufferUsages::COPY_DST,
+        })
+    }
+
+    /// Writes `contents` to `buffer`, or replaces it if they don't fit.
+    fn write_mesh_buffer(
+        st: IntermediateState,
+        buffer: &mut Buffer,
+        capacity: &mut BufferAddress,
+        contents: &[u8],
+        usage: BufferUsages,
+    ) {
+        if contents.len() as BufferAddress <= *capacity {
+            st.queue.write_buffer(buffer, 0, contents);
+        } else {
+            *buffer = Self::create_mesh_buffer(st.device, contents, usage);
+            *capacity = contents.len() as BufferAddress;
+        }
+    }
+
+    /// Room for the static geometry and `capacity` objects.
+    fn create_object_buffer(device: &Device, capacity: usize, stride: BufferAddress) -> Buffer {
+        device.create_buffer(&BufferDescriptor {
             label: None,
-            layout: &prerender_output_bind_group_layout,
+            size: (capacity + 1) as BufferAddress * stride,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_object_bind_group(
+        device: &Device,
+        layout: &BindGroupLayout,
+        object_buffer: &Buffer,
+    ) -> BindGroup {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::Buffer(BufferBinding {
+                    buffer: object_buffer,
+                    offset: 0,
+                    size: NonZeroU64::new(size_of::<ObjectUniforms>() as u64),
+                }),
+            }],
+        })
+    }
+
+    /// Uploads the static vertices and the object meshes, reusing the vertex and index buffers
+    /// when they fit.
+    fn upload_meshes(&mut self, st: IntermediateState) {
+        let meshes = || std::iter::once(&self.vertices).chain(self.objects.iter().map(|o| &o.mesh));
+        let vertex_bytes: Vec<u8> = meshes()
+            .flat_map(|mesh| mesh.vertex_bytes())
+            .copied()
+            .collect();
+        let index_bytes: Vec<u8> = meshes()
+            .flat_map(|mesh| mesh.index_bytes())
+            .copied()
+            .collect();
+        Self::write_mesh_buffer(
+            st,
+            &mut self.vertex_buffer,
+            &mut self.vertex_buffer_capacity,
+            &vertex_bytes,
+            BufferUsages::VERTEX,
+        );
+        Self::write_mesh_buffer(
+            st,
+            &mut self.index_buffer,
+            &mut self.index_buffer_capacity,
+            &index_bytes,
+            BufferUsages::INDEX,
+        );
+    }
+
+    /// Replaces the static scene geometry.
+    pub fn set_vertices(&mut self, st: IntermediateState, vertices: VertexList) {
+        self.vertices = vertices;
+        self.upload_meshes(st);
+    }
+
+    /// Replaces the objects, uploading their meshes.
+    pub fn set_objects(&mut self, st: IntermediateState, objects: Vec<Object>) {
+        self.objects = objects;
+        self.upload_meshes(st);
+        if self.objects.len() > self.object_buffer_capacity {
+            self.object_buffer_capacity = self.objects.len();
+            self.object_buffer = Self::create_object_buffer(
+                st.device,
+                self.object_buffer_capacity,
+                self.object_stride,
+            );
+            self.object_bind_group = Self::create_object_bind_group(
+                st.device,
+                &self.object_bind_group_layout,
+                &self.object_buffer,
+            );
+        }
+    }
+
+    /// Replaces the texels drawn under the triangles, or removes them.
+    pub fn set_bitmap(
+        &mut self,
+        st: IntermediateState,
+        bitmap: Option<&Bitmap>,
+    ) -> Result<(), RenderError> {
+        self.bitmap = bitmap
+            .map(|bitmap| BitmapTextures::new(st, &self.bitmap_bind_group_layout, bitmap))
+            .transpose()?;
+        Ok(())
+    }
+
+    /// The properties of the first object called `name`. Changes show from the next frame.
+    pub fn object_properties_mut(&mut self, name: &str) -> Option<&mut ObjectProperties> {
+        self.objects
+            .iter_mut()
+            .find(|object| object.name == name)
+            .map(|object| &mut object.properties)
+    }
+
+    fn create_output_bind_group(
+        device: &Device,
+        layout: &BindGroupLayout,
+        prerender_textures: &PrerenderTextures,
+    ) -> BindGroup {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout,
             entries: &[
                 BindGroupEntry {
                     binding: 0,
@@ -178,28 +585,93 @@ impl PrerenderState {
                     resource: BindingResource::TextureView(&prerender_textures.normal.1),
                 },
             ],
-        });
+        })
+    }
 
-        Self {
-            vertices,
-            vertex_buffer,
-            prerender_textures,
-            prerender_pipeline,
-            prerender_output_bind_group_layout,
-            prerender_output_bind_group,
-        }
+    pub fn resize(&mut self, st: IntermediateState) {
+        self.prerender_textures = PrerenderTextures::new(st.device, st.size);
+        self.guide_textures = Self::create_guide_textures(st);
+        self.prerender_output_bind_group = Self::create_output_bind_group(
+            st.device,
+            &self.prerender_output_bind_group_layout,
+            &self.prerender_textures,
+        );
     }
 
     pub fn render(st: &mut State, encoder: &mut CommandEncoder) {
-        let mut prerender_pass = encoder.begin_render_pass(&RenderPassDescriptor {
-            label: None,
-            color_attachments: &st.prerender_state.prerender_textures.attachments(),
-            depth_stencil_attachment: None,
-        });
+        let state = &st.prerender_state;
+        let stride = state.object_stride as usize;
+        let mut object_uniforms = vec![0; (state.objects.len() + 1) * stride];
+        let static_properties = ObjectProperties::default();
+        let properties = std::iter::once(&static_properties)
+            .chain(state.objects.iter().map(|object| &object.properties));
+        for (bytes, properties) in object_uniforms.chunks_exact_mut(stride).zip(properties) {
+            let uniforms = ObjectUniforms::from(properties);
+            bytes[..size_of::<ObjectUniforms>()].copy_from_slice(bytemuck::bytes_of(&uniforms));
+        }
+        st.queue
+            .write_buffer(&state.object_buffer, 0, &object_uniforms);
+
+        let meshes = || {
+            std::iter::once(&state.vertices).chain(state.objects.iter().map(|object| &object.mesh))
+        };
+        // With a bitmap, the triangle pipeline only runs for triangles to draw over it.
+        let has_triangles = meshes().any(|mesh| mesh.len() > 0);
+
+        // The vertex shader maps the scene to the whole target, whatever its resolution.
+        for textures in std::iter::once(&state.prerender_textures).chain(&state.guide_textures) {
+            if let Some(bitmap) = &state.bitmap {
+                let mut bitmap_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                    label: None,
+                    color_attachments: &textures.attachments(true),
+                    depth_stencil_attachment: None,
+                });
+                bitmap_pass.set_pipeline(&state.bitmap_pipeline);
+                bitmap_pass.set_vertex_buffer(0, st.fullscreen_buffer.slice(..));
+                bitmap_pass.set_bind_group(0, &st.global_uniforms.bind_group, &[]);
+                bitmap_pass.set_bind_group(1, &bitmap.bind_group, &[]);
+                bitmap_pass.draw(0..3, 0..1);
+                if !has_triangles {
+                    continue;
+                }
+            }
+
+            let mut prerender_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: None,
+                color_attachments: &textures.attachments(state.bitmap.is_none()),
+                depth_stencil_attachment: None,
+            });
+
+            prerender_pass.set_pipeline(&state.prerender_pipeline);
+            prerender_pass.set_vertex_buffer(0, state.vertex_buffer.slice(..));
+            prerender_pass.set_index_buffer(state.index_buffer.slice(..), IndexFormat::Uint32);
+            prerender_pass.set_bind_group(0, &st.global_uniforms.bind_group, &[]);
+            // Every object is its own draw, with the offset of its properties.
+            let (mut start, mut base_vertex) = (0, 0);
+            for (offset, mesh) in (0..).step_by(stride).zip(meshes()) {
+                let end = start + mesh.len();
+                prerender_pass.set_bind_group(1, &state.object_bind_group, &[offset]);
+                prerender_pass.draw_indexed(start..end, base_vertex, 0..1);
+                start = end;
+                base_vertex += mesh.vertex_count() as i32;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::vector;
+
+    use super::*;
 
-        prerender_pass.set_pipeline(&st.prerender_state.prerender_pipeline);
-        prerender_pass.set_vertex_buffer(0, st.prerender_state.vertex_buffer.slice(..));
-        prerender_pass.set_bind_group(0, &st.global_uniforms.bind_group, &[]);
-        prerender_pass.draw(0..st.prerender_state.vertices.len(), 0..1);
+    #[test]
+    fn bitmaps_fit_the_device() {
+        assert!(check_texture_size(vector![8192, 1], 8192).is_ok());
+        assert!(matches!(
+            check_texture_size(vector![8193, 1], 8192),
+            Err(RenderError::BitmapTooLarge { size, max: 8192 }) if size == vector![8193, 1]
+        ));
+        assert!(check_texture_size(vector![1, 8193], 8192).is_err());
     }
 }
@@ -2,19 +2,21 @@ use bitflags::bitflags;
 use bytemuck::{bytes_of, cast_slice, Pod, Zeroable};
 use nalgebra::Vector2;
 use palette::LinSrgb;
-use std::f32::consts::TAU;
+use std::f32::consts::{PI, TAU};
+use std::fmt::{self, Display, Formatter};
 use std::mem::size_of;
 use std::num::NonZeroU32;
-use wgpu::util::{BufferInitDescriptor, DeviceExt};
 use wgpu::*;
 
 use super::prerender::PrerenderState;
 use super::{IntermediateState, State};
+use crate::environment::Environment;
 use crate::texture::TextureWithView;
 use crate::uniform::UniformData;
 
-const RADIANCE_WORKGROUP_SIZE: u32 = 16;
-const MAX_LIGHT_DIRECTIONS: usize = 64;
+pub const RADIANCE_WORKGROUP_SIZE: u32 = 16;
+/// Fractional part of the golden ratio, which spreads the jitter evenly over any number of frames.
+const JITTER_STEP: f32 = 0.618_034;
 
 #[derive(Debug)]
 pub struct RadianceTextures {
@@ -54,6 +56,7 @@ impl RadianceTextures {
                 TextureFormat::Rgba32Float,
                 TextureUsages::TEXTURE_BINDING
                     | TextureUsages::RENDER_ATTACHMENT
+                    | TextureUsages::COPY_SRC
                     | TextureUsages::COPY_DST,
             ),
         }
@@ -69,7 +72,7 @@ pub struct RadianceCollectUniforms {
 bitflags! {
     #[repr(C)]
     #[derive(Pod, Zeroable, Default)]
-    struct RadianceDirectionFlags: u32 {
+    pub struct RadianceDirectionFlags: u32 {
         const REVERSE_DIRECTION = 0b01;
         const VERTICAL_TRACING = 0b10;
     }
@@ -78,45 +81,124 @@ bitflags! {
 #[repr(C)]
 #[derive(Debug, Clone, Copy, Default, Pod, Zeroable)]
 pub struct RadianceDirectionalUniforms {
-    slope: f32,
-    flags: RadianceDirectionFlags,
+    pub slope: f32,
+    pub flags: RadianceDirectionFlags,
     _padding: [u32; 2],
-    starting_radiance: LinSrgb,
+    pub starting_radiance: LinSrgb,
     _padding_2: u32,
 }
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy, Pod, Zeroable)]
 pub struct RadianceUniforms {
-    inv_light_directions: f32,
-    _padding: [u32; 3],
-    directional_uniforms: [RadianceDirectionalUniforms; MAX_LIGHT_DIRECTIONS],
+    pub inv_light_directions: f32,
+    pub num_workgroups: u32,
+    pub workgroups_per_row: u32,
+    _padding: u32,
 }
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy, Pod, Zeroable)]
-struct Workgroup {
-    offset: i32,
-    light_direction_index: u32,
+pub struct Workgroup {
+    pub offset: i32,
+    pub light_direction_index: u32,
 }
 
 #[derive(Debug, Copy, Clone)]
 pub struct RadianceSettings {
     pub light_directions: u32,
     pub light_bounces: u32,
+    /// Blend every frame into a history texture instead of showing it directly.
     pub temporal_accumulate: bool,
+    /// Lower bound on the weight of the newest frame when accumulating. The history is a
+    /// progressive average until `1 / frames` drops below this, then an exponential moving
+    /// average. Zero keeps averaging forever.
+    pub temporal_blend: f32,
+    /// Rotate all light directions by a different fraction of the angle between them every
+    /// frame, which turns the streaks between directions into noise that accumulation averages out.
+    pub direction_jitter: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RadianceSettingsError {
+    NoLightDirections,
+    TooManyLightDirections { light_directions: u32, max: u32 },
+    NoLightBounces,
+    InvalidTemporalBlend(f32),
+}
+
+impl Display for RadianceSettingsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            RadianceSettingsError::NoLightDirections => {
+                write!(f, "light_directions must be at least 1")
+            }
+            RadianceSettingsError::TooManyLightDirections {
+                light_directions,
+                max,
+            } => write!(
+                f,
+                "{} light_directions requested, but the device supports at most {}",
+                light_directions, max
+            ),
+            RadianceSettingsError::NoLightBounces => write!(f, "light_bounces must be at least 1"),
+            RadianceSettingsError::InvalidTemporalBlend(blend) => {
+                write!(f, "temporal_blend must be between 0 and 1, got {}", blend)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RadianceSettingsError {}
+
+impl RadianceSettings {
+    /// Checks the settings against what the device can allocate. Each light direction is a
+    /// layer of the directional radiance texture.
+    pub fn validate(&self, limits: &Limits) -> Result<(), RadianceSettingsError> {
+        if self.light_directions == 0 {
+            return Err(RadianceSettingsError::NoLightDirections);
+        }
+        if self.light_directions > limits.max_texture_array_layers {
+            return Err(RadianceSettingsError::TooManyLightDirections {
+                light_directions: self.light_directions,
+                max: limits.max_texture_array_layers,
+            });
+        }
+        if self.light_bounces == 0 {
+            return Err(RadianceSettingsError::NoLightBounces);
+        }
+        if !(0.0..=1.0).contains(&self.temporal_blend) {
+            return Err(RadianceSettingsError::InvalidTemporalBlend(
+                self.temporal_blend,
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct RadianceUniformBuffers {
+    uniforms: Buffer,
+    directional_uniforms: Buffer,
+    workgroups: Buffer,
 }
 
 #[derive(Debug)]
 pub struct RadianceState {
-    light_bounces: u32,
-    temporal_accumulate: bool,
-    radiance_textures: RadianceTextures,
+    settings: RadianceSettings,
+    environment: Environment,
+    pub radiance_textures: RadianceTextures,
     radiance_collect_uniforms: UniformData<RadianceCollectUniforms>,
+    radiance_collect_bind_group_layout: BindGroupLayout,
     radiance_collect_bind_group: BindGroup,
     radiance_collect_pipeline: RenderPipeline,
+    radiance_uniforms_bind_group_layout: BindGroupLayout,
+    radiance_uniform_buffers: RadianceUniformBuffers,
     radiance_uniforms_bind_group: BindGroup,
     num_workgroups: u32,
+    workgroups_per_row: u32,
+    frame: u32,
+    jitter: f32,
     pub radiance_bind_group_layout: BindGroupLayout,
     pub radiance_bind_group: BindGroup,
     radiance_pipeline: ComputePipeline,
@@ -127,15 +209,15 @@ impl RadianceState {
         st: IntermediateState,
         prerender_state: &PrerenderState,
         settings: RadianceSettings,
-    ) -> Self {
-        assert!(settings.light_bounces >= 1);
+    ) -> Result<Self, RadianceSettingsError> {
+        settings.validate(&st.device.limits())?;
 
         let radiance_textures =
             RadianceTextures::new(st.device, settings.light_directions, st.size);
 
         let radiance_collect_uniforms = UniformData::new(
             st.device,
-            false,
+            true,
             ShaderStages::COMPUTE | ShaderStages::FRAGMENT,
             RadianceCollectUniforms {
                 light_directions: settings.light_directions,
@@ -157,14 +239,11 @@ impl RadianceState {
                         count: None,
                     }],
                 });
-        let radiance_collect_bind_group = st.device.create_bind_group(&BindGroupDescriptor {
-            label: None,
-            layout: &radiance_collect_bind_group_layout,
-            entries: &[BindGroupEntry {
-                binding: 0,
-                resource: BindingResource::TextureView(&radiance_textures.directional_radiance.1),
-            }],
-        });
+        let radiance_collect_bind_group = Self::create_collect_bind_group(
+            st.device,
+            &radiance_collect_bind_group_layout,
+            &radiance_textures,
+        );
 
         let radiance_collect_pipeline_layout =
             st.device.create_pipeline_layout(&PipelineLayoutDescriptor {
@@ -220,21 +299,6 @@ impl RadianceState {
                 multiview: None,
             });
 
-        let (radiance_uniforms, workgroups) =
-            Self::compute_radiance_uniforms(st, settings.light_directions);
-
-        let radiance_uniform_buffer = st.device.create_buffer_init(&BufferInitDescriptor {
-            label: None,
-            contents: bytes_of(&radiance_uniforms),
-            usage: BufferUsages::UNIFORM,
-        });
-
-        let workgroups_buffer = st.device.create_buffer_init(&BufferInitDescriptor {
-            label: None,
-            contents: cast_slice(&workgroups),
-            usage: BufferUsages::STORAGE,
-        });
-
         let radiance_uniforms_bind_group_layout =
             st.device
                 .create_bind_group_layout(&BindGroupLayoutDescriptor {
@@ -260,23 +324,26 @@ impl RadianceState {
                             },
                             count: None,
                         },
+                        BindGroupLayoutEntry {
+                            binding: 2,
+                            visibility: ShaderStages::COMPUTE,
+                            ty: BindingType::Buffer {
+                                ty: BufferBindingType::Storage { read_only: false },
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            count: None,
+                        },
                     ],
                 });
 
-        let radiance_uniforms_bind_group = st.device.create_bind_group(&BindGroupDescriptor {
-            label: None,
-            layout: &radiance_uniforms_bind_group_layout,
-            entries: &[
-                BindGroupEntry {
-                    binding: 0,
-                    resource: radiance_uniform_buffer.as_entire_binding(),
-                },
-                BindGroupEntry {
-                    binding: 1,
-                    resource: workgroups_buffer.as_entire_binding(),
-                },
-            ],
-        });
+        let radiance_uniform_buffers =
+            Self::create_uniform_buffers(st.device, st.size, settings.light_directions);
+        let radiance_uniforms_bind_group = Self::create_uniforms_bind_group(
+            st.device,
+            &radiance_uniforms_bind_group_layout,
+            &radiance_uniform_buffers,
+        );
 
         let radiance_bind_group_layout =
             st.device
@@ -306,22 +373,11 @@ impl RadianceState {
                     ],
                 });
 
-        let radiance_bind_group = st.device.create_bind_group(&BindGroupDescriptor {
-            label: None,
-            layout: &radiance_bind_group_layout,
-            entries: &[
-                BindGroupEntry {
-                    binding: 0,
-                    resource: BindingResource::TextureView(&radiance_textures.total_radiance.1),
-                },
-                BindGroupEntry {
-                    binding: 1,
-                    resource: BindingResource::TextureView(
-                        &radiance_textures.directional_radiance.1,
-                    ),
-                },
-            ],
-        });
+        let radiance_bind_group = Self::create_radiance_bind_group(
+            st.device,
+            &radiance_bind_group_layout,
+            &radiance_textures,
+        );
 
         let radiance_pipeline_layout =
             st.device.create_pipeline_layout(&PipelineLayoutDescriptor {
@@ -346,33 +402,253 @@ impl RadianceState {
                 entry_point: "main",
             });
 
-        Self {
-            light_bounces: settings.light_bounces,
-            temporal_accumulate: settings.temporal_accumulate,
+        let mut radiance_state = Self {
+            settings,
+            environment: Environment::default(),
             radiance_textures,
             radiance_collect_uniforms,
+            radiance_collect_bind_group_layout,
             radiance_collect_bind_group,
             radiance_collect_pipeline,
+            radiance_uniforms_bind_group_layout,
+            radiance_uniform_buffers,
             radiance_uniforms_bind_group,
-            num_workgroups: workgroups.len() as u32,
+            num_workgroups: 0,
+            workgroups_per_row: 0,
+            frame: 0,
+            jitter: 0.0,
             radiance_bind_group_layout,
             radiance_bind_group,
             radiance_pipeline,
+        };
+        radiance_state.write_radiance_uniforms(st);
+        Ok(radiance_state)
+    }
+
+    fn create_collect_bind_group(
+        device: &Device,
+        layout: &BindGroupLayout,
+        radiance_textures: &RadianceTextures,
+    ) -> BindGroup {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(&radiance_textures.directional_radiance.1),
+            }],
+        })
+    }
+
+    /// Creates the buffers for `RadianceUniforms`, the per-direction uniforms and the
+    /// workgroups, with room for the most workgroups any rotation of the light directions can need.
+    fn create_uniform_buffers(
+        device: &Device,
+        size: Vector2<u32>,
+        light_directions: u32,
+    ) -> RadianceUniformBuffers {
+        let uniforms = device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: size_of::<RadianceUniforms>() as BufferAddress,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let directional_uniforms = device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: (light_directions as usize * size_of::<RadianceDirectionalUniforms>())
+                as BufferAddress,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        // A line with a slope of at most 1 crosses at most `size.x + size.y` offsets.
+        let max_workgroups =
+            light_directions * (size.x + size.y + 1).div_ceil(RADIANCE_WORKGROUP_SIZE);
+        let workgroups = device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: (max_workgroups as usize * size_of::<Workgroup>()) as BufferAddress,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        RadianceUniformBuffers {
+            uniforms,
+            directional_uniforms,
+            workgroups,
         }
     }
 
-    fn compute_radiance_uniforms(
+    fn create_uniforms_bind_group(
+        device: &Device,
+        layout: &BindGroupLayout,
+        buffers: &RadianceUniformBuffers,
+    ) -> BindGroup {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: buffers.uniforms.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: buffers.workgroups.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: buffers.directional_uniforms.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    /// Uploads the lines to trace for the light directions rotated by the current jitter, and
+    /// the light entering along them. Also needed after the camera rotated, as the environment
+    /// turns with the view.
+    pub fn write_radiance_uniforms(&mut self, st: IntermediateState) {
+        let (directional_uniforms, workgroups) = Self::compute_radiance_uniforms(
+            st.size,
+            self.settings.light_directions,
+            self.jitter,
+            &self.environment,
+            st.global_uniforms.data.camera_rotation,
+        );
+
+        // Dispatches are limited per dimension, so lay the workgroups out in rows.
+        self.num_workgroups = workgroups.len() as u32;
+        self.workgroups_per_row = self
+            .num_workgroups
+            .clamp(1, st.device.limits().max_compute_workgroups_per_dimension);
+        let radiance_uniforms = RadianceUniforms {
+            inv_light_directions: 1.0 / self.settings.light_directions as f32,
+            num_workgroups: self.num_workgroups,
+            workgroups_per_row: self.workgroups_per_row,
+            _padding: 0,
+        };
+
+        let buffers = &self.radiance_uniform_buffers;
+        st.queue
+            .write_buffer(&buffers.uniforms, 0, bytes_of(&radiance_uniforms));
+        st.queue.write_buffer(
+            &buffers.directional_uniforms,
+            0,
+            cast_slice(&directional_uniforms),
+        );
+        st.queue
+            .write_buffer(&buffers.workgroups, 0, cast_slice(&workgroups));
+    }
+
+    fn create_radiance_bind_group(
+        device: &Device,
+        layout: &BindGroupLayout,
+        radiance_textures: &RadianceTextures,
+    ) -> BindGroup {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&radiance_textures.total_radiance.1),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(
+                        &radiance_textures.directional_radiance.1,
+                    ),
+                },
+            ],
+        })
+    }
+
+    /// Recreates everything that depends on the window size.
+    pub fn resize(&mut self, st: IntermediateState) {
+        self.radiance_textures =
+            RadianceTextures::new(st.device, self.settings.light_directions, st.size);
+        self.radiance_collect_bind_group = Self::create_collect_bind_group(
+            st.device,
+            &self.radiance_collect_bind_group_layout,
+            &self.radiance_textures,
+        );
+        self.radiance_uniform_buffers =
+            Self::create_uniform_buffers(st.device, st.size, self.settings.light_directions);
+        self.radiance_uniforms_bind_group = Self::create_uniforms_bind_group(
+            st.device,
+            &self.radiance_uniforms_bind_group_layout,
+            &self.radiance_uniform_buffers,
+        );
+        self.write_radiance_uniforms(st);
+        self.radiance_bind_group = Self::create_radiance_bind_group(
+            st.device,
+            &self.radiance_bind_group_layout,
+            &self.radiance_textures,
+        );
+    }
+
+    pub fn settings(&self) -> RadianceSettings {
+        self.settings
+    }
+
+    /// Applies new settings, reallocating only what depends on the settings that changed.
+    pub fn set_settings(
+        &mut self,
         st: IntermediateState,
+        settings: RadianceSettings,
+    ) -> Result<(), RadianceSettingsError> {
+        settings.validate(&st.device.limits())?;
+        let light_directions_changed = settings.light_directions != self.settings.light_directions;
+        self.settings = settings;
+        if !settings.direction_jitter {
+            self.frame = 0;
+            self.jitter = 0.0;
+        }
+
+        if light_directions_changed {
+            self.radiance_collect_uniforms
+                .update(st.queue, RadianceCollectUniforms {
+                    light_directions: settings.light_directions,
+                });
+            // The textures and buffers sized by the window are also sized by the directions.
+            self.resize(st);
+        } else {
+            self.write_radiance_uniforms(st);
+        }
+        Ok(())
+    }
+
+    pub fn environment(&self) -> &Environment {
+        &self.environment
+    }
+
+    pub fn set_environment(&mut self, st: IntermediateState, environment: Environment) {
+        self.environment = environment;
+        self.write_radiance_uniforms(st);
+    }
+
+    /// The rotation of the light directions used by the last frame, as a fraction of the angle
+    /// between two of them.
+    pub fn jitter(&self) -> f32 {
+        self.jitter
+    }
+
+    /// Computes the slope and flags of every light direction, and the lines each workgroup traces.
+    ///
+    /// `jitter` in `[0, 1)` rotates every direction by that fraction of the angle between two.
+    /// Each line starts with the light `environment` sends along its direction, seen through a
+    /// camera rotated by `camera_rotation`.
+    pub fn compute_radiance_uniforms(
+        size: Vector2<u32>,
         light_directions: u32,
-    ) -> (RadianceUniforms, Vec<Workgroup>) {
+        jitter: f32,
+        environment: &Environment,
+        camera_rotation: f32,
+    ) -> (Vec<RadianceDirectionalUniforms>, Vec<Workgroup>) {
         let mut workgroups = Vec::new();
-        let mut radiance_uniforms = RadianceUniforms {
-            inv_light_directions: 1.0 / (light_directions as f32),
-            _padding: [0; 3],
-            directional_uniforms: [Default::default(); MAX_LIGHT_DIRECTIONS],
-        };
+        let mut directional_uniforms = Vec::with_capacity(light_directions as usize);
         for i in 0..light_directions {
-            let direction = i as f32 / light_directions as f32 - 0.5;
+            let direction = (i as f32 + jitter) / light_directions as f32 - 0.5;
 
             // +----x  -0.375   -0.25   -0.125
             // |            *.r+v | v-r.*
@@ -409,21 +685,27 @@ impl RadianceState {
                 is_direction_reversed,
             );
             let axies = if flags.contains(RadianceDirectionFlags::VERTICAL_TRACING) {
-                st.size.yx()
+                size.yx()
             } else {
-                st.size
+                size
             };
             let offset = -slope * axies.x as f32;
             let offset = (offset + offset.signum() * 0.999) as i32;
             let total_size = offset.abs() as u32 + axies.y;
             let offset = offset.min(0);
-            radiance_uniforms.directional_uniforms[i as usize] = RadianceDirectionalUniforms {
+            // `direction` is the angle the light travels in, with y pointing down. Like the
+            // directional radiance, the environment light is split evenly between the directions.
+            let environment_angle = PI - direction * TAU + camera_rotation;
+            let starting_radiance = environment
+                .average_radiance(environment_angle, TAU / light_directions as f32)
+                / light_directions as f32;
+            directional_uniforms.push(RadianceDirectionalUniforms {
                 slope,
                 flags,
                 _padding: [0; 2],
-                starting_radiance: LinSrgb::new(0.0, 0.0, 0.0),
+                starting_radiance,
                 _padding_2: 0,
-            };
+            });
             let num_workgroups =
                 (total_size + RADIANCE_WORKGROUP_SIZE - 1) / RADIANCE_WORKGROUP_SIZE;
             for wg in 0..num_workgroups {
@@ -433,33 +715,38 @@ impl RadianceState {
                 });
             }
         }
-        (radiance_uniforms, workgroups)
+        (directional_uniforms, workgroups)
     }
 
     pub fn render(st: &mut State, encoder: &mut CommandEncoder) {
-        if !st.radiance_state.temporal_accumulate {
-            encoder.copy_texture_to_texture(
-                ImageCopyTexture {
-                    texture: &st.prerender_state.prerender_textures.radiance_lin.0,
-                    mip_level: 0,
-                    origin: Origin3d::ZERO,
-                    aspect: TextureAspect::All,
-                },
-                ImageCopyTexture {
-                    texture: &st.radiance_state.radiance_textures.total_radiance.0,
-                    mip_level: 0,
-                    origin: Origin3d::ZERO,
-                    aspect: TextureAspect::All,
-                },
-                Extent3d {
-                    width: st.size.x,
-                    height: st.size.y,
-                    depth_or_array_layers: 1,
-                },
-            );
+        let (intermediate_state, _, radiance_state, _, _, _) = st.split_mut();
+        if radiance_state.settings.direction_jitter {
+            radiance_state.jitter = (radiance_state.frame as f32 * JITTER_STEP).fract();
+            radiance_state.frame = radiance_state.frame.wrapping_add(1);
+            radiance_state.write_radiance_uniforms(intermediate_state);
         }
 
-        for _ in 0..st.radiance_state.light_bounces {
+        encoder.copy_texture_to_texture(
+            ImageCopyTexture {
+                texture: &st.prerender_state.prerender_textures.radiance_lin.0,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            ImageCopyTexture {
+                texture: &st.radiance_state.radiance_textures.total_radiance.0,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            Extent3d {
+                width: st.size.x,
+                height: st.size.y,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        for _ in 0..st.radiance_state.settings.light_bounces {
             let mut radiance_pass =
                 encoder.begin_compute_pass(&ComputePassDescriptor { label: None });
 
@@ -468,7 +755,14 @@ impl RadianceState {
             radiance_pass.set_bind_group(1, &st.radiance_state.radiance_uniforms_bind_group, &[]);
             radiance_pass.set_bind_group(2, &st.prerender_state.prerender_output_bind_group, &[]);
             radiance_pass.set_bind_group(3, &st.radiance_state.radiance_bind_group, &[]);
-            radiance_pass.dispatch(st.radiance_state.num_workgroups, 1, 1);
+            let workgroups_per_row = st.radiance_state.workgroups_per_row;
+            radiance_pass.dispatch(
+                workgroups_per_row,
+                st.radiance_state
+                    .num_workgroups
+                    .div_ceil(workgroups_per_row),
+                1,
+            );
 
             drop(radiance_pass);
 
@@ -1,27 +1,187 @@
+use bytemuck::{Pod, Zeroable};
 use nalgebra::Vector2;
 use std::mem::size_of;
 use wgpu::*;
 
+use super::accumulate::AccumulateState;
+use super::exposure::{AutoExposureSettings, ExposureState};
 use super::prerender::PrerenderState;
 use super::radiance::RadianceState;
 use super::{IntermediateState, State};
+use crate::uniform::UniformData;
+
+/// The curve that maps radiance to the displayable range.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToneMapping {
+    /// Clamps to 1, so anything brighter clips to white.
+    Linear = 0,
+    Reinhard = 1,
+    Aces = 2,
+    Agx = 3,
+}
+
+/// What the render pass shows, for looking at the intermediate buffers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViewMode {
+    Final,
+    Albedo,
+    /// `radiance_lin`, the light emitted by the scene.
+    Emitted,
+    Normal,
+    /// The alpha of the albedo, which decides how much light passes through.
+    Alpha,
+    /// One layer of `directional_radiance`, scaled by the number of directions.
+    DirectionalRadiance(u32),
+    /// False color of the final luminance, from -8 to +8 stops.
+    Heatmap,
+}
+
+/// How the render pass fills the surface when the scene is rendered at a lower resolution.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Upsampling {
+    Nearest = 0,
+    Bilinear = 1,
+    /// Bilinear, but ignoring texels whose albedo differs from the full resolution albedo, so
+    /// light doesn't bleed across edges.
+    EdgeAware = 2,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RenderSettings {
+    pub tone_mapping: ToneMapping,
+    /// In stops, applied before tone mapping. With auto exposure, this is compensation on top.
+    pub exposure: f32,
+    pub auto_exposure: Option<AutoExposureSettings>,
+    pub view_mode: ViewMode,
+    /// Fraction of the physical surface resolution the scene is rendered at, up to 1 for the
+    /// full resolution of the window.
+    pub render_scale: f32,
+    pub upsampling: Upsampling,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            tone_mapping: ToneMapping::Aces,
+            exposure: 0.0,
+            auto_exposure: None,
+            view_mode: ViewMode::Final,
+            render_scale: 1.0,
+            upsampling: Upsampling::EdgeAware,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+pub struct RenderUniforms {
+    exposure: f32,
+    tone_mapping: u32,
+    encode_srgb: u32,
+    auto_exposure: u32,
+    view_mode: u32,
+    direction_layer: u32,
+    upsampling: u32,
+}
+
+impl RenderUniforms {
+    fn new(settings: RenderSettings, format: TextureFormat) -> Self {
+        Self {
+            exposure: settings.exposure.exp2(),
+            tone_mapping: settings.tone_mapping as u32,
+            encode_srgb: !format.describe().srgb as u32,
+            auto_exposure: settings.auto_exposure.is_some() as u32,
+            view_mode: match settings.view_mode {
+                ViewMode::Final => 0,
+                ViewMode::Albedo => 1,
+                ViewMode::Emitted => 2,
+                ViewMode::Normal => 3,
+                ViewMode::Alpha => 4,
+                ViewMode::DirectionalRadiance(_) => 5,
+                ViewMode::Heatmap => 6,
+            },
+            direction_layer: match settings.view_mode {
+                ViewMode::DirectionalRadiance(layer) => layer,
+                _ => 0,
+            },
+            upsampling: settings.upsampling as u32,
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct RenderState {
+    settings: RenderSettings,
+    render_uniforms: UniformData<RenderUniforms>,
+    debug_bind_group_layout: BindGroupLayout,
+    debug_bind_group: BindGroup,
     render_pipeline: RenderPipeline,
 }
 
+fn texture_entry(
+    binding: u32,
+    sample_type: TextureSampleType,
+    view_dimension: TextureViewDimension,
+) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding,
+        visibility: ShaderStages::FRAGMENT,
+        ty: BindingType::Texture {
+            sample_type,
+            view_dimension,
+            multisampled: false,
+        },
+        count: None,
+    }
+}
+
 impl RenderState {
     pub fn new(
         st: IntermediateState,
-        _prerender_state: &PrerenderState,
+        prerender_state: &PrerenderState,
         radiance_state: &RadianceState,
+        accumulate_state: &AccumulateState,
+        exposure_state: &ExposureState,
+        settings: RenderSettings,
     ) -> Self {
+        let render_uniforms = UniformData::new(
+            st.device,
+            true,
+            ShaderStages::FRAGMENT,
+            RenderUniforms::new(settings, st.config.format),
+        );
+
+        // The intermediate buffers, for the view modes.
+        let float = TextureSampleType::Float { filterable: false };
+        let debug_bind_group_layout =
+            st.device
+                .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                    label: None,
+                    entries: &[
+                        texture_entry(0, float, TextureViewDimension::D2),
+                        texture_entry(1, float, TextureViewDimension::D2),
+                        texture_entry(2, float, TextureViewDimension::D2),
+                        texture_entry(3, TextureSampleType::Uint, TextureViewDimension::D2Array),
+                        texture_entry(4, float, TextureViewDimension::D2),
+                    ],
+                });
+        let debug_bind_group = Self::create_debug_bind_group(
+            st.device,
+            &debug_bind_group_layout,
+            prerender_state,
+            radiance_state,
+        );
+
         let render_pipeline_layout = st.device.create_pipeline_layout(&PipelineLayoutDescriptor {
             label: None,
             bind_group_layouts: &[
                 &st.global_uniforms.bind_group_layout,
-                &radiance_state.radiance_bind_group_layout,
+                &accumulate_state.output_bind_group_layout,
+                &render_uniforms.bind_group_layout,
+                &exposure_state.output_bind_group_layout,
+                &debug_bind_group_layout,
             ],
             push_constant_ranges: &[],
         });
@@ -67,18 +227,87 @@ impl RenderState {
             multiview: None,
         });
 
-        Self { render_pipeline }
+        Self {
+            settings,
+            render_uniforms,
+            debug_bind_group_layout,
+            debug_bind_group,
+            render_pipeline,
+        }
+    }
+
+    fn create_debug_bind_group(
+        device: &Device,
+        layout: &BindGroupLayout,
+        prerender_state: &PrerenderState,
+        radiance_state: &RadianceState,
+    ) -> BindGroup {
+        let prerender_textures = &prerender_state.prerender_textures;
+        // Without guide textures the scene is already at the surface resolution.
+        let guide_textures = prerender_state
+            .guide_textures
+            .as_ref()
+            .unwrap_or(prerender_textures);
+        device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&prerender_textures.albedo_lin.1),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&prerender_textures.radiance_lin.1),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::TextureView(&prerender_textures.normal.1),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: BindingResource::TextureView(
+                        &radiance_state.radiance_textures.directional_radiance.1,
+                    ),
+                },
+                BindGroupEntry {
+                    binding: 4,
+                    resource: BindingResource::TextureView(&guide_textures.albedo_lin.1),
+                },
+            ],
+        })
+    }
+
+    /// Recreates the bind group after the prerender or radiance textures were reallocated.
+    pub fn rebind(
+        &mut self,
+        st: IntermediateState,
+        prerender_state: &PrerenderState,
+        radiance_state: &RadianceState,
+    ) {
+        self.debug_bind_group = Self::create_debug_bind_group(
+            st.device,
+            &self.debug_bind_group_layout,
+            prerender_state,
+            radiance_state,
+        );
     }
 
-    pub fn render(st: &mut State, encoder: &mut CommandEncoder, output: &SurfaceTexture) {
-        let view = output
-            .texture
-            .create_view(&TextureViewDescriptor::default());
+    pub fn settings(&self) -> RenderSettings {
+        self.settings
+    }
+
+    pub fn set_settings(&mut self, st: IntermediateState, settings: RenderSettings) {
+        self.settings = settings;
+        self.render_uniforms
+            .update(st.queue, RenderUniforms::new(settings, st.config.format));
+    }
 
+    pub fn render(st: &State, encoder: &mut CommandEncoder, view: &TextureView) {
         let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
             label: None,
             color_attachments: &[RenderPassColorAttachment {
-                view: &view,
+                view,
                 resolve_target: None,
                 ops: Operations {
                     load: LoadOp::Clear(Color {
@@ -96,7 +325,10 @@ impl RenderState {
         render_pass.set_pipeline(&st.render_state.render_pipeline);
         render_pass.set_vertex_buffer(0, st.fullscreen_buffer.slice(..));
         render_pass.set_bind_group(0, &st.global_uniforms.bind_group, &[]);
-        render_pass.set_bind_group(1, &st.radiance_state.radiance_bind_group, &[]);
+        render_pass.set_bind_group(1, &st.accumulate_state.output_bind_group, &[]);
+        render_pass.set_bind_group(2, &st.render_state.render_uniforms.bind_group, &[]);
+        render_pass.set_bind_group(3, &st.exposure_state.output_bind_group, &[]);
+        render_pass.set_bind_group(4, &st.render_state.debug_bind_group, &[]);
         render_pass.draw(0..3, 0..1);
     }
 }
@@ -0,0 +1,1037 @@
+use nalgebra::{Matrix3, Point2, Vector2};
+use palette::Srgb;
+use std::f32::consts::TAU;
+use std::fmt::{self, Display, Formatter};
+use xml::attribute::OwnedAttribute;
+use xml::reader::{EventReader, XmlEvent};
+
+use crate::scene::{Color, Shape};
+use crate::vertex::is_simple_polygon;
+
+/// Default distance in logical pixels that flattened curves may stray from the real ones.
+pub const DEFAULT_TOLERANCE: f32 = 0.25;
+
+#[derive(Debug)]
+pub enum SvgError {
+    Xml(xml::reader::Error),
+    Invalid { element: String, reason: String },
+}
+
+impl Display for SvgError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SvgError::Xml(error) => write!(f, "{}", error),
+            SvgError::Invalid { element, reason } => write!(f, "<{}>: {}", element, reason),
+        }
+    }
+}
+
+impl std::error::Error for SvgError {}
+
+/// The inherited presentation properties, and the transform to world coordinates.
+#[derive(Debug, Clone, Copy)]
+struct Style {
+    fill: Option<Color>,
+    fill_opacity: f32,
+    stroke: Option<Color>,
+    stroke_opacity: f32,
+    stroke_width: f32,
+    /// The product of the `opacity` of the element and its ancestors.
+    opacity: f32,
+    /// The `radiance` property, scaled by `radiance-intensity` once linear.
+    radiance: Color,
+    radiance_intensity: f32,
+    transform: Matrix3<f32>,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            fill: Some(Color(0.0, 0.0, 0.0)),
+            fill_opacity: 1.0,
+            stroke: None,
+            stroke_opacity: 1.0,
+            stroke_width: 1.0,
+            opacity: 1.0,
+            radiance: Color::default(),
+            radiance_intensity: 1.0,
+            transform: Matrix3::identity(),
+        }
+    }
+}
+
+/// The attributes of an element, with declarations in `style` taking precedence.
+struct Attributes<'a> {
+    attributes: &'a [OwnedAttribute],
+    style: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> Attributes<'a> {
+    fn new(attributes: &'a [OwnedAttribute]) -> Self {
+        let mut this = Self {
+            attributes,
+            style: Vec::new(),
+        };
+        if let Some(style) = this.attribute("style") {
+            this.style = style
+                .split(';')
+                .filter_map(|declaration| declaration.split_once(':'))
+                .map(|(name, value)| (name.trim(), value.trim()))
+                .collect();
+        }
+        this
+    }
+
+    fn attribute(&self, name: &str) -> Option<&'a str> {
+        self.attributes
+            .iter()
+            .find(|attribute| {
+                attribute.name.local_name == name && attribute.name.namespace.is_none()
+            })
+            .map(|attribute| attribute.value.as_str())
+    }
+
+    fn property(&self, name: &str) -> Option<&'a str> {
+        self.style
+            .iter()
+            .rev()
+            .find(|(property, _)| *property == name)
+            .map(|&(_, value)| value)
+            .or_else(|| self.attribute(name))
+    }
+}
+
+/// Splits SVG number lists and path data, where separators are optional whenever the next
+/// number can't be confused with the previous one.
+struct Tokens<'a> {
+    source: &'a str,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(source: &'a str) -> Self {
+        Self { source }
+    }
+
+    fn skip_separators(&mut self) {
+        self.source = self
+            .source
+            .trim_start_matches(|c: char| c.is_ascii_whitespace() || c == ',');
+    }
+
+    fn is_empty(&mut self) -> bool {
+        self.skip_separators();
+        self.source.is_empty()
+    }
+
+    /// The next path command letter, if there is one before the next number.
+    fn command(&mut self) -> Option<char> {
+        self.skip_separators();
+        let c = self
+            .source
+            .chars()
+            .next()
+            .filter(char::is_ascii_alphabetic)?;
+        self.source = &self.source[1..];
+        Some(c)
+    }
+
+    fn number(&mut self) -> Result<f32, String> {
+        self.skip_separators();
+        let bytes = self.source.as_bytes();
+        let mut end = 0;
+        let digits = |end: &mut usize| {
+            while matches!(bytes.get(*end), Some(b) if b.is_ascii_digit()) {
+                *end += 1;
+            }
+        };
+        if matches!(bytes.first(), Some(b'+' | b'-')) {
+            end += 1;
+        }
+        digits(&mut end);
+        if bytes.get(end) == Some(&b'.') {
+            end += 1;
+            digits(&mut end);
+        }
+        if matches!(bytes.get(end), Some(b'e' | b'E')) {
+            let mantissa = end;
+            end += 1;
+            if matches!(bytes.get(end), Some(b'+' | b'-')) {
+                end += 1;
+            }
+            let exponent = end;
+            digits(&mut end);
+            if end == exponent {
+                end = mantissa;
+            }
+        }
+        let (number, rest) = self.source.split_at(end);
+        let number = number
+            .parse()
+            .map_err(|_| format!("expected a number at {:?}", self.source))?;
+        self.source = rest;
+        Ok(number)
+    }
+
+    fn point(&mut self) -> Result<Vector2<f32>, String> {
+        Ok(Vector2::new(self.number()?, self.number()?))
+    }
+
+    /// An arc flag, which is a single digit that may run into the next number.
+    fn flag(&mut self) -> Result<bool, String> {
+        self.skip_separators();
+        let flag = match self.source.as_bytes().first() {
+            Some(b'0') => false,
+            Some(b'1') => true,
+            _ => return Err(format!("expected a flag at {:?}", self.source)),
+        };
+        self.source = &self.source[1..];
+        Ok(flag)
+    }
+}
+
+fn parse_number(value: &str) -> Result<f32, String> {
+    let number = value.trim();
+    let number = number.strip_suffix("px").unwrap_or(number);
+    match number.parse::<f32>() {
+        Ok(x) if x.is_finite() => Ok(x),
+        _ => Err(format!("expected a number, got {:?}", value)),
+    }
+}
+
+fn parse_channel(value: &str) -> Result<f32, String> {
+    match value.trim().strip_suffix('%') {
+        Some(percent) => Ok(parse_number(percent)? / 100.0),
+        None => Ok(parse_number(value)? / 255.0),
+    }
+}
+
+/// Parses a color, or `None` for `none`.
+fn parse_color(value: &str) -> Result<Option<Color>, String> {
+    let value = value.trim();
+    let hex = |digits: &str| u8::from_str_radix(digits, 16).ok();
+    let color = |[r, g, b]: [u8; 3]| Some(Color(r as f32, g as f32, b as f32));
+    let parsed = if let Some(digits) = value.strip_prefix('#') {
+        match digits.len() {
+            3 => (|| {
+                let channel = |i: usize| Some(hex(&digits[i..i + 1])? * 17);
+                color([channel(0)?, channel(1)?, channel(2)?])
+            })(),
+            6 => (|| {
+                color([
+                    hex(&digits[0..2])?,
+                    hex(&digits[2..4])?,
+                    hex(&digits[4..6])?,
+                ])
+            })(),
+            _ => None,
+        }
+        .map(|Color(r, g, b)| Color(r / 255.0, g / 255.0, b / 255.0))
+    } else if let Some(channels) = value
+        .strip_prefix("rgb(")
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        let channels = channels
+            .split(',')
+            .map(parse_channel)
+            .collect::<Result<Vec<_>, _>>()?;
+        match channels[..] {
+            [r, g, b] => Some(Color(r, g, b)),
+            _ => None,
+        }
+    } else {
+        let name = match value {
+            "none" | "transparent" => return Ok(None),
+            "black" => [0, 0, 0],
+            "white" => [255, 255, 255],
+            "gray" | "grey" => [128, 128, 128],
+            "silver" => [192, 192, 192],
+            "red" => [255, 0, 0],
+            "lime" => [0, 255, 0],
+            "green" => [0, 128, 0],
+            "blue" => [0, 0, 255],
+            "yellow" => [255, 255, 0],
+            "cyan" | "aqua" => [0, 255, 255],
+            "magenta" | "fuchsia" => [255, 0, 255],
+            "orange" => [255, 165, 0],
+            _ => return Err(format!("unsupported color {:?}", value)),
+        };
+        color(name).map(|Color(r, g, b)| Color(r / 255.0, g / 255.0, b / 255.0))
+    };
+    parsed
+        .map(Some)
+        .ok_or_else(|| format!("invalid color {:?}", value))
+}
+
+fn parse_opacity(value: &str) -> Result<f32, String> {
+    Ok(parse_number(value)?.clamp(0.0, 1.0))
+}
+
+/// Parses a list of `matrix`, `translate`, `scale`, `rotate`, `skewX` and `skewY`.
+fn parse_transform(value: &str) -> Result<Matrix3<f32>, String> {
+    let mut transform = Matrix3::identity();
+    let mut rest = value.trim();
+    while !rest.is_empty() {
+        let (name, arguments) = rest
+            .split_once('(')
+            .ok_or_else(|| format!("invalid transform {:?}", value))?;
+        let (arguments, after) = arguments
+            .split_once(')')
+            .ok_or_else(|| format!("invalid transform {:?}", value))?;
+        let mut tokens = Tokens::new(arguments);
+        let mut numbers = Vec::new();
+        while !tokens.is_empty() {
+            numbers.push(tokens.number()?);
+        }
+        #[rustfmt::skip]
+        let matrix = |a, b, c, d, e, f| Matrix3::new(
+            a, c, e,
+            b, d, f,
+            0.0, 0.0, 1.0,
+        );
+        let rotation = |angle: f32, cx: f32, cy: f32| {
+            let (sin, cos) = angle.to_radians().sin_cos();
+            matrix(1.0, 0.0, 0.0, 1.0, cx, cy)
+                * matrix(cos, sin, -sin, cos, 0.0, 0.0)
+                * matrix(1.0, 0.0, 0.0, 1.0, -cx, -cy)
+        };
+        let step = match (name.trim().trim_start_matches(','), &numbers[..]) {
+            ("matrix", &[a, b, c, d, e, f]) => matrix(a, b, c, d, e, f),
+            ("translate", &[x]) => matrix(1.0, 0.0, 0.0, 1.0, x, 0.0),
+            ("translate", &[x, y]) => matrix(1.0, 0.0, 0.0, 1.0, x, y),
+            ("scale", &[s]) => matrix(s, 0.0, 0.0, s, 0.0, 0.0),
+            ("scale", &[x, y]) => matrix(x, 0.0, 0.0, y, 0.0, 0.0),
+            ("rotate", &[angle]) => rotation(angle, 0.0, 0.0),
+            ("rotate", &[angle, cx, cy]) => rotation(angle, cx, cy),
+            ("skewX", &[angle]) => matrix(1.0, 0.0, angle.to_radians().tan(), 1.0, 0.0, 0.0),
+            ("skewY", &[angle]) => matrix(1.0, angle.to_radians().tan(), 0.0, 1.0, 0.0, 0.0),
+            _ => return Err(format!("invalid transform {:?}", value)),
+        };
+        transform *= step;
+        rest = after.trim_start_matches(|c: char| c.is_ascii_whitespace() || c == ',');
+    }
+    Ok(transform)
+}
+
+impl Style {
+    /// The style of a child element with `attributes`.
+    fn child(&self, attributes: &Attributes) -> Result<Self, String> {
+        let mut style = Self {
+            opacity: 1.0,
+            ..*self
+        };
+        if let Some(value) = attributes.property("fill") {
+            style.fill = parse_color(value)?;
+        }
+        if let Some(value) = attributes.property("fill-opacity") {
+            style.fill_opacity = parse_opacity(value)?;
+        }
+        if let Some(value) = attributes.property("stroke") {
+            style.stroke = parse_color(value)?;
+        }
+        if let Some(value) = attributes.property("stroke-opacity") {
+            style.stroke_opacity = parse_opacity(value)?;
+        }
+        if let Some(value) = attributes.property("stroke-width") {
+            style.stroke_width = parse_number(value)?;
+        }
+        if let Some(value) = attributes.property("opacity") {
+            style.opacity = parse_opacity(value)?;
+        }
+        style.opacity *= self.opacity;
+        if let Some(value) = attributes.property("radiance") {
+            style.radiance = parse_color(value)?.unwrap_or_default();
+        }
+        if let Some(value) = attributes.property("radiance-intensity") {
+            style.radiance_intensity = parse_number(value)?;
+        }
+        if let Some(value) = attributes.attribute("transform") {
+            style.transform *= parse_transform(value)?;
+        }
+        Ok(style)
+    }
+
+    fn point(&self, point: Vector2<f32>) -> [f32; 2] {
+        let point = self.transform.transform_point(&Point2::from(point));
+        [point.x, point.y]
+    }
+
+    fn vector(&self, vector: Vector2<f32>) -> Vector2<f32> {
+        self.transform.transform_vector(&vector)
+    }
+
+    /// The most the transform stretches any length, so curves flattened before it still stay
+    /// within the tolerance after it.
+    fn max_scale(&self) -> f32 {
+        self.transform
+            .fixed_slice::<2, 2>(0, 0)
+            .into_owned()
+            .singular_values()
+            .max()
+    }
+
+    /// The emitted radiance as a scene color. The intensity scales the linear radiance, so it
+    /// is converted back to sRGB afterwards.
+    fn radiance(&self) -> Color {
+        let linear = Srgb::from(self.radiance).into_linear() * self.radiance_intensity;
+        let Srgb {
+            red, green, blue, ..
+        } = Srgb::from_linear(linear);
+        Color(red, green, blue)
+    }
+
+    /// The fill as a polygon through `points`, if it is filled. Fills that cross themselves or
+    /// have no area can't be polygons and are left out with a warning.
+    fn fill_polygon(&self, points: &[Vector2<f32>], warnings: &mut Vec<String>) -> Option<Shape> {
+        let albedo = self.fill?;
+        let points: Vec<_> = points.iter().map(|&point| self.point(point)).collect();
+        let corners: Vec<Vector2<f32>> = points.iter().map(|&point| point.into()).collect();
+        if !is_simple_polygon(&corners) {
+            warnings.push("the fill crosses itself or has no area, so it is left out".to_string());
+            return None;
+        }
+        Some(Shape::Polygon {
+            points,
+            albedo,
+            alpha: self.opacity * self.fill_opacity,
+            radiance: self.radiance(),
+        })
+    }
+
+    /// The stroke along `points`, if it is stroked. The width scales with the transform.
+    fn stroke_line(&self, points: &[Vector2<f32>], closed: bool) -> Option<Shape> {
+        let albedo = self.stroke?;
+        let scale = self
+            .transform
+            .fixed_slice::<2, 2>(0, 0)
+            .determinant()
+            .abs()
+            .sqrt();
+        let thickness = self.stroke_width * scale;
+        let mut points: Vec<_> = points.iter().map(|&point| self.point(point)).collect();
+        if closed {
+            points.extend(points.first().copied());
+        }
+        (thickness > 0.0 && !points.is_empty()).then(|| Shape::Line {
+            points,
+            thickness,
+            albedo,
+            alpha: self.opacity * self.stroke_opacity,
+            radiance: self.radiance(),
+        })
+    }
+}
+
+/// Segments for a curve of `radius` to stay within `tolerance`.
+fn circle_segments(radius: f32, tolerance: f32) -> u32 {
+    let step = 2.0 * (1.0 - (tolerance / radius).min(1.0)).acos();
+    ((TAU / step).ceil() as u32).clamp(8, 256)
+}
+
+/// Points along a cubic Bézier curve after `p0`, close enough to it for `tolerance`.
+fn flatten_cubic(
+    points: &mut Vec<Vector2<f32>>,
+    [p0, p1, p2, p3]: [Vector2<f32>; 4],
+    tolerance: f32,
+) {
+    // The second derivative bounds how far the chords stray from the curve.
+    let bend = (p0 - 2.0 * p1 + p2).norm().max((p1 - 2.0 * p2 + p3).norm());
+    let n = ((0.75 * bend / tolerance).sqrt().ceil() as u32).clamp(1, 256);
+    points.extend((1..=n).map(|i| {
+        let t = i as f32 / n as f32;
+        let s = 1.0 - t;
+        p0 * (s * s * s) + p1 * (3.0 * s * s * t) + p2 * (3.0 * s * t * t) + p3 * (t * t * t)
+    }));
+}
+
+fn flatten_quadratic(
+    points: &mut Vec<Vector2<f32>>,
+    [p0, p1, p2]: [Vector2<f32>; 3],
+    tolerance: f32,
+) {
+    let bend = (p0 - 2.0 * p1 + p2).norm();
+    let n = ((0.25 * bend / tolerance).sqrt().ceil() as u32).clamp(1, 256);
+    points.extend((1..=n).map(|i| {
+        let t = i as f32 / n as f32;
+        let s = 1.0 - t;
+        p0 * (s * s) + p1 * (2.0 * s * t) + p2 * (t * t)
+    }));
+}
+
+/// Points along an elliptical arc from `p0` to `p1`, given like the SVG arc command with the
+/// ellipse turned by `rotation` degrees.
+fn flatten_arc(
+    points: &mut Vec<Vector2<f32>>,
+    [p0, p1]: [Vector2<f32>; 2],
+    radii: Vector2<f32>,
+    rotation: f32,
+    [large_arc, sweep]: [bool; 2],
+    tolerance: f32,
+) {
+    if p0 == p1 {
+        return;
+    }
+    let (mut rx, mut ry) = (radii.x.abs(), radii.y.abs());
+    if rx == 0.0 || ry == 0.0 {
+        points.push(p1);
+        return;
+    }
+    // Follows the endpoint to center conversion of the SVG specification, in the frame of the
+    // ellipse.
+    let (sin, cos) = rotation.to_radians().sin_cos();
+    let rotate = |v: Vector2<f32>| Vector2::new(cos * v.x - sin * v.y, sin * v.x + cos * v.y);
+    let half = (p0 - p1) / 2.0;
+    let p = Vector2::new(cos * half.x + sin * half.y, -sin * half.x + cos * half.y);
+    // Radii too small to reach the end are scaled up until they just do.
+    let reach = (p.x / rx).powi(2) + (p.y / ry).powi(2);
+    if reach > 1.0 {
+        rx *= reach.sqrt();
+        ry *= reach.sqrt();
+    }
+    let (rx2, ry2) = (rx * rx, ry * ry);
+    let numerator = (rx2 * ry2 - rx2 * p.y * p.y - ry2 * p.x * p.x).max(0.0);
+    let mut factor = (numerator / (rx2 * p.y * p.y + ry2 * p.x * p.x)).sqrt();
+    if large_arc == sweep {
+        factor = -factor;
+    }
+    let center = factor * Vector2::new(rx * p.y / ry, -ry * p.x / rx);
+    let angle = |v: Vector2<f32>| ((v.y - center.y) / ry).atan2((v.x - center.x) / rx);
+    let start = angle(p);
+    let mut delta = angle(-p) - start;
+    if sweep && delta < 0.0 {
+        delta += TAU;
+    } else if !sweep && delta > 0.0 {
+        delta -= TAU;
+    }
+    let center = rotate(center) + (p0 + p1) / 2.0;
+
+    let segments = circle_segments(rx.max(ry), tolerance) as f32 * delta.abs() / TAU;
+    let n = (segments.ceil() as u32).max(1);
+    points.extend((1..n).map(|i| {
+        let angle = start + delta * i as f32 / n as f32;
+        center + rotate(Vector2::new(rx * angle.cos(), ry * angle.sin()))
+    }));
+    // End exactly on `p1`, so closing the path finds its start again.
+    points.push(p1);
+}
+
+/// The points of a subpath, and whether it was closed.
+type Subpath = (Vec<Vector2<f32>>, bool);
+
+/// Flattens path data into subpaths, each with whether it was closed.
+fn parse_path(data: &str, tolerance: f32) -> Result<Vec<Subpath>, String> {
+    let mut tokens = Tokens::new(data);
+    let mut subpaths = Vec::new();
+    let mut points: Vec<Vector2<f32>> = Vec::new();
+    let mut position = Vector2::zeros();
+    // The last control point, reflected by the smooth curve commands.
+    let mut control: Option<(char, Vector2<f32>)> = None;
+    let mut command = None;
+    let finish = |points: &mut Vec<Vector2<f32>>, subpaths: &mut Vec<_>, closed| {
+        if points.len() > 1 {
+            subpaths.push((std::mem::take(points), closed));
+        }
+        points.clear();
+    };
+    while !tokens.is_empty() {
+        command = match tokens.command() {
+            Some(c) => Some(c),
+            // Coordinates after a moveto are implicit linetos.
+            None => match command {
+                Some('M') => Some('L'),
+                Some('m') => Some('l'),
+                Some('Z' | 'z') | None => return Err(format!("expected a command in {:?}", data)),
+                repeated => repeated,
+            },
+        };
+        let c = command.unwrap();
+        let upper = c.to_ascii_uppercase();
+        let origin = if c.is_ascii_lowercase() {
+            position
+        } else {
+            Vector2::zeros()
+        };
+        let reflected = |kind: char| match control {
+            Some((last, point)) if last == kind => 2.0 * position - point,
+            _ => position,
+        };
+        let mut next_control = None;
+        match upper {
+            'M' => {
+                finish(&mut points, &mut subpaths, false);
+                position = origin + tokens.point()?;
+                points.push(position);
+            }
+            'L' => position = origin + tokens.point()?,
+            'H' => position.x = origin.x + tokens.number()?,
+            'V' => position.y = origin.y + tokens.number()?,
+            'C' | 'S' => {
+                let p1 = if upper == 'C' {
+                    origin + tokens.point()?
+                } else {
+                    reflected('C')
+                };
+                let p2 = origin + tokens.point()?;
+                let p3 = origin + tokens.point()?;
+                flatten_cubic(&mut points, [position, p1, p2, p3], tolerance);
+                next_control = Some(('C', p2));
+                position = p3;
+            }
+            'Q' | 'T' => {
+                let p1 = if upper == 'Q' {
+                    origin + tokens.point()?
+                } else {
+                    reflected('Q')
+                };
+                let p2 = origin + tokens.point()?;
+                flatten_quadratic(&mut points, [position, p1, p2], tolerance);
+                next_control = Some(('Q', p1));
+                position = p2;
+            }
+            'A' => {
+                let radii = tokens.point()?;
+                let rotation = tokens.number()?;
+                let flags = [tokens.flag()?, tokens.flag()?];
+                let end = origin + tokens.point()?;
+                flatten_arc(
+                    &mut points,
+                    [position, end],
+                    radii,
+                    rotation,
+                    flags,
+                    tolerance,
+                );
+                position = end;
+            }
+            'Z' => {
+                if let Some(&start) = points.first() {
+                    position = start;
+                }
+                finish(&mut points, &mut subpaths, true);
+                points.push(position);
+            }
+            _ => return Err(format!("unsupported path command {:?}", c)),
+        }
+        if matches!(upper, 'L' | 'H' | 'V') {
+            points.push(position);
+        }
+        control = next_control;
+    }
+    finish(&mut points, &mut subpaths, false);
+    for (points, _) in &mut subpaths {
+        points.dedup();
+        // Closing a path often repeats its first point.
+        if points.len() > 1 && points.first() == points.last() {
+            points.pop();
+        }
+    }
+    Ok(subpaths)
+}
+
+fn parse_points(value: &str) -> Result<Vec<Vector2<f32>>, String> {
+    let mut tokens = Tokens::new(value);
+    let mut points = Vec::new();
+    while !tokens.is_empty() {
+        points.push(tokens.point()?);
+    }
+    Ok(points)
+}
+
+/// Adds the shapes for one element, and a warning for every part of it that can't be drawn
+/// as it should.
+fn add_element(
+    shapes: &mut Vec<Shape>,
+    warnings: &mut Vec<String>,
+    element: &str,
+    attributes: &Attributes,
+    style: &Style,
+    tolerance: f32,
+) -> Result<(), String> {
+    let number = |name: &str| attributes.attribute(name).map_or(Ok(0.0), parse_number);
+    match element {
+        "rect" => {
+            let (x, y) = (number("x")?, number("y")?);
+            let (width, height) = (number("width")?, number("height")?);
+            if width <= 0.0 || height <= 0.0 {
+                return Ok(());
+            }
+            if let Some(albedo) = style.fill {
+                let center = style.point(Vector2::new(x + width / 2.0, y + height / 2.0));
+                let half_x = style.vector(Vector2::new(width / 2.0, 0.0));
+                let mut half_y = style.vector(Vector2::new(0.0, height / 2.0));
+                // `parallelogram` expects y counterclockwise from x.
+                if half_x.perp(&half_y) < 0.0 {
+                    half_y = -half_y;
+                }
+                shapes.push(Shape::Parallelogram {
+                    center,
+                    x: half_x.into(),
+                    y: half_y.into(),
+                    albedo,
+                    alpha: style.opacity * style.fill_opacity,
+                    radiance: style.radiance(),
+                });
+            }
+            let corners = [
+                (x, y),
+                (x + width, y),
+                (x + width, y + height),
+                (x, y + height),
+            ];
+            let corners = corners.map(|(x, y)| Vector2::new(x, y));
+            shapes.extend(style.stroke_line(&corners, true));
+        }
+        "circle" | "ellipse" => {
+            let center = Vector2::new(number("cx")?, number("cy")?);
+            let (rx, ry) = if element == "circle" {
+                (number("r")?, number("r")?)
+            } else {
+                (number("rx")?, number("ry")?)
+            };
+            if rx <= 0.0 || ry <= 0.0 {
+                return Ok(());
+            }
+            let x = style.vector(Vector2::new(rx, 0.0));
+            let y = style.vector(Vector2::new(0.0, ry));
+            let segments = circle_segments(x.norm().max(y.norm()), tolerance);
+            if let Some(albedo) = style.fill {
+                shapes.push(Shape::Ellipse {
+                    center: style.point(center),
+                    x: x.into(),
+                    y: y.into(),
+                    segments,
+                    albedo,
+                    alpha: style.opacity * style.fill_opacity,
+                    radiance: style.radiance(),
+                });
+            }
+            let outline: Vec<_> = (0..segments)
+                .map(|i| {
+                    let angle = i as f32 / segments as f32 * TAU;
+                    center + Vector2::new(rx * angle.cos(), ry * angle.sin())
+                })
+                .collect();
+            shapes.extend(style.stroke_line(&outline, true));
+        }
+        "line" => {
+            let a = Vector2::new(number("x1")?, number("y1")?);
+            let b = Vector2::new(number("x2")?, number("y2")?);
+            shapes.extend(style.stroke_line(&[a, b], false));
+        }
+        "polyline" | "polygon" => {
+            let points = parse_points(attributes.attribute("points").unwrap_or(""))?;
+            shapes.extend(style.fill_polygon(&points, warnings));
+            shapes.extend(style.stroke_line(&points, element == "polygon"));
+        }
+        "path" => {
+            // The path is flattened in user units, before the transform scales it.
+            let data = attributes.attribute("d").unwrap_or("");
+            let subpaths = parse_path(data, tolerance / style.max_scale())?;
+            // Every subpath is filled on its own, so holes aren't cut out.
+            if style.fill.is_some() && subpaths.len() > 1 {
+                warnings.push(format!(
+                    "each of the {} subpaths is filled on its own, without cutting out holes",
+                    subpaths.len()
+                ));
+            }
+            for (points, closed) in subpaths {
+                shapes.extend(style.fill_polygon(&points, warnings));
+                shapes.extend(style.stroke_line(&points, closed));
+            }
+        }
+        _ => unreachable!(),
+    }
+    Ok(())
+}
+
+/// Converts the shapes of an SVG document to scene shapes, with curves flattened to within
+/// `tolerance`. SVG user units become logical pixels, with the y axis flipped so the top of the
+/// view box stays on top.
+///
+/// Supports `rect` (without rounded corners), `circle`, `ellipse`, `line`, `polyline`,
+/// `polygon` and `path`, in nested `g` elements with transforms. The fill color is the albedo
+/// and the opacity its alpha. The `radiance` color, with its linear value times
+/// `radiance-intensity`, is the emitted radiance; both can be attributes or `style` properties.
+/// Fills that cross themselves are skipped with a warning, and so are the holes of paths. Other
+/// elements are skipped along with their content, and so are other properties.
+pub fn import(source: &str, tolerance: f32) -> Result<Vec<Shape>, SvgError> {
+    let mut warnings = Vec::new();
+    let shapes = import_with_warnings(source, tolerance, &mut warnings)?;
+    for warning in warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    Ok(shapes)
+}
+
+/// Like `import`, collecting the warnings, each starting with its element and id.
+fn import_with_warnings(
+    source: &str,
+    tolerance: f32,
+    warnings: &mut Vec<String>,
+) -> Result<Vec<Shape>, SvgError> {
+    let mut shapes = Vec::new();
+    let mut styles: Vec<Style> = Vec::new();
+    // Depth inside an element whose content isn't drawn, like `defs`.
+    let mut skipped = 0;
+    for event in EventReader::from_str(source) {
+        match event.map_err(SvgError::Xml)? {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                let element = name.local_name.as_str();
+                let invalid = |reason| SvgError::Invalid {
+                    element: element.to_string(),
+                    reason,
+                };
+                let attributes = Attributes::new(&attributes);
+                if skipped > 0 {
+                    skipped += 1;
+                    continue;
+                }
+                let parent = match styles.last() {
+                    Some(style) => *style,
+                    None if element == "svg" => Style {
+                        transform: flip(&attributes).map_err(invalid)?,
+                        ..Style::default()
+                    },
+                    None => return Err(invalid("the root element must be <svg>".to_string())),
+                };
+                let style = parent.child(&attributes).map_err(invalid)?;
+                match element {
+                    "svg" | "g" => {}
+                    "rect" | "circle" | "ellipse" | "line" | "polyline" | "polygon" | "path" => {
+                        let mut element_warnings = Vec::new();
+                        add_element(
+                            &mut shapes,
+                            &mut element_warnings,
+                            element,
+                            &attributes,
+                            &style,
+                            tolerance,
+                        )
+                        .map_err(invalid)?;
+                        // The id tells apart elements of the same kind.
+                        let name = match attributes.attribute("id") {
+                            Some(id) => format!("<{} id={:?}>", element, id),
+                            None => format!("<{}>", element),
+                        };
+                        warnings.extend(
+                            element_warnings
+                                .into_iter()
+                                .map(|warning| format!("{}: {}", name, warning)),
+                        );
+                    }
+                    _ => skipped = 1,
+                }
+                styles.push(style);
+            }
+            XmlEvent::EndElement { .. } => {
+                if skipped > 0 {
+                    skipped -= 1;
+                }
+                if skipped == 0 {
+                    styles.pop();
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(shapes)
+}
+
+/// The transform from the user units of the root `svg` element to world coordinates.
+fn flip(attributes: &Attributes) -> Result<Matrix3<f32>, String> {
+    let (min, height) = match attributes.attribute("viewBox") {
+        Some(view_box) => match parse_points(view_box)?[..] {
+            [min, size] => (min, size.y),
+            _ => return Err(format!("invalid viewBox {:?}", view_box)),
+        },
+        None => (
+            Vector2::zeros(),
+            attributes
+                .attribute("height")
+                .map_or(Ok(0.0), parse_number)?,
+        ),
+    };
+    #[rustfmt::skip]
+    let flip = Matrix3::new(
+        1.0, 0.0, -min.x,
+        0.0, -1.0, min.y + height,
+        0.0, 0.0, 1.0,
+    );
+    Ok(flip)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{} is not close to {}", a, b);
+    }
+
+    #[test]
+    fn tokens_without_separators() {
+        let mut tokens = Tokens::new("M1.5-2e1,.5.25 1e+2L-3");
+        assert_eq!(tokens.command(), Some('M'));
+        assert_eq!(tokens.command(), None);
+        assert_eq!(tokens.point(), Ok(Vector2::new(1.5, -20.0)));
+        assert_eq!(tokens.point(), Ok(Vector2::new(0.5, 0.25)));
+        assert_eq!(tokens.number(), Ok(100.0));
+        assert!(tokens.number().is_err());
+        assert_eq!(tokens.command(), Some('L'));
+        assert_eq!(tokens.number(), Ok(-3.0));
+        assert!(tokens.is_empty());
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(parse_number(" 12px"), Ok(12.0));
+        assert_eq!(parse_number("-0.5"), Ok(-0.5));
+        assert!(parse_number("1em").is_err());
+        assert!(parse_number("inf").is_err());
+    }
+
+    #[test]
+    fn colors() {
+        assert_eq!(parse_color("#fff"), Ok(Some(Color(1.0, 1.0, 1.0))));
+        assert_eq!(parse_color("#ff0000"), Ok(Some(Color(1.0, 0.0, 0.0))));
+        assert_eq!(
+            parse_color("rgb(255, 0, 50%)"),
+            Ok(Some(Color(1.0, 0.0, 0.5)))
+        );
+        assert_eq!(parse_color(" lime "), Ok(Some(Color(0.0, 1.0, 0.0))));
+        assert_eq!(parse_color("none"), Ok(None));
+        assert!(parse_color("#ff00").is_err());
+        assert!(parse_color("rgb(1, 2)").is_err());
+        assert!(parse_color("rebeccapurple").is_err());
+    }
+
+    #[test]
+    fn transforms() {
+        let transform = parse_transform("translate(10, 20) scale(2) rotate(90)").unwrap();
+        let point = transform.transform_point(&Point2::new(1.0, 0.0));
+        assert_close(point.x, 10.0);
+        assert_close(point.y, 22.0);
+
+        let transform = parse_transform("rotate(180 5 5),matrix(1 0 0 1 1 1)").unwrap();
+        let point = transform.transform_point(&Point2::new(0.0, 0.0));
+        assert_close(point.x, 9.0);
+        assert_close(point.y, 9.0);
+
+        assert!(parse_transform("scale(1, 2, 3)").is_err());
+        assert!(parse_transform("translate(1").is_err());
+        assert!(parse_transform("spin(90)").is_err());
+    }
+
+    #[test]
+    fn arc_flags_run_into_numbers() {
+        let mut tokens = Tokens::new("0 1,0 1.5");
+        assert_eq!(tokens.number(), Ok(0.0));
+        assert_eq!(tokens.flag(), Ok(true));
+        assert_eq!(tokens.flag(), Ok(false));
+        assert_eq!(tokens.number(), Ok(1.5));
+        assert!(Tokens::new("2").flag().is_err());
+    }
+
+    #[test]
+    fn paths() {
+        let subpaths = parse_path("M0 0h10v10H0z m20 0 l5 5 5-5", DEFAULT_TOLERANCE).unwrap();
+        assert_eq!(subpaths, vec![
+            (
+                vec![
+                    Vector2::new(0.0, 0.0),
+                    Vector2::new(10.0, 0.0),
+                    Vector2::new(10.0, 10.0),
+                    Vector2::new(0.0, 10.0),
+                ],
+                true
+            ),
+            (
+                vec![
+                    Vector2::new(20.0, 0.0),
+                    Vector2::new(25.0, 5.0),
+                    Vector2::new(30.0, 0.0),
+                ],
+                false
+            ),
+        ]);
+        assert!(parse_path("1 1", DEFAULT_TOLERANCE).is_err());
+        assert!(parse_path("M0 0 X", DEFAULT_TOLERANCE).is_err());
+    }
+
+    #[test]
+    fn arcs_are_flattened() {
+        // A half circle of radius 10 around (10, 0), and the flags picking each half.
+        for (flags, y) in [("0 1", -10.0), ("0 0", 10.0), ("1 1", -10.0)] {
+            let data = format!("M0 0 A10 10 0 {} 20 0", flags);
+            let subpaths = parse_path(&data, 0.01).unwrap();
+            let points = &subpaths[0].0;
+            assert!(points.len() > 8);
+            assert_eq!(points.last(), Some(&Vector2::new(20.0, 0.0)));
+            for point in points {
+                assert_close((point - Vector2::new(10.0, 0.0)).norm(), 10.0);
+            }
+            let middle = points[points.len() / 2];
+            assert!((middle.y - y).abs() < 1.0, "{} gave {:?}", flags, middle);
+        }
+
+        // Radii too small to reach the end are scaled up, to a half ellipse here.
+        let subpaths = parse_path("M0 0 a1 2 0 0 0 4 0", DEFAULT_TOLERANCE).unwrap();
+        for point in &subpaths[0].0 {
+            let relative = point - Vector2::new(2.0, 0.0);
+            assert_close((relative.x / 2.0).powi(2) + (relative.y / 4.0).powi(2), 1.0);
+            assert!(point.y >= -1e-4);
+        }
+    }
+
+    #[test]
+    fn radiance_intensity_scales_linear_radiance() {
+        let source = r##"<svg width="10" height="10">
+            <rect width="10" height="10" radiance="#808080" radiance-intensity="4"/>
+        </svg>"##;
+        let shapes = import(source, DEFAULT_TOLERANCE).unwrap();
+        let radiance = match &shapes[..] {
+            [Shape::Parallelogram { radiance, .. }] => *radiance,
+            shapes => panic!("unexpected shapes {:?}", shapes),
+        };
+        let linear = Srgb::from(radiance).into_linear();
+        let expected = Srgb::new(128.0 / 255.0, 0.0, 0.0).into_linear().red * 4.0;
+        assert_close(linear.red, expected);
+        assert_close(linear.blue, expected);
+    }
+
+    #[test]
+    fn paths_are_flattened_for_their_transform() {
+        let path = |transform| {
+            let source = format!(
+                r#"<svg width="10" height="10">
+                    <path transform="{}" d="M0 0 C0 1 1 1 1 0" fill="none" stroke="black"/>
+                </svg>"#,
+                transform
+            );
+            match &import(&source, 0.1).unwrap()[..] {
+                [Shape::Line { points, .. }] => points.len(),
+                shapes => panic!("unexpected shapes {:?}", shapes),
+            }
+        };
+        assert!(path("scale(100)") > path("scale(1)") * 5);
+        // Stretching only one axis counts too.
+        assert!(path("scale(1 100)") > path("scale(1)") * 5);
+    }
+
+    #[test]
+    fn dropped_fills_are_warned_about() {
+        let source = r#"<svg width="10" height="10">
+            <polygon id="bowtie" points="0 0 2 2 2 0 0 2" fill="red"/>
+            <path d="M0 0h10v10H0z M2 2h6v6H2z" fill="red"/>
+            <path d="M0 0h10v10H0z M2 2h6v6H2z" fill="none" stroke="red"/>
+        </svg>"#;
+        let mut warnings = Vec::new();
+        let shapes = import_with_warnings(source, DEFAULT_TOLERANCE, &mut warnings).unwrap();
+        // Both squares of the filled path, and both outlines of the stroked one.
+        assert_eq!(shapes.len(), 4);
+        assert_eq!(warnings.len(), 2, "{:?}", warnings);
+        assert!(warnings[0].starts_with("<polygon id=\"bowtie\">: "));
+        assert!(warnings[1].starts_with("<path>: each of the 2 subpaths"));
+    }
+}
@@ -51,10 +51,24 @@ impl<T: Clone + Copy + Pod + Zeroable> UniformData<T> {
             bind_group,
         }
     }
+
+    pub fn update(&mut self, queue: &Queue, data: T) {
+        self.data = data;
+        queue.write_buffer(&self.buffer, 0, bytes_of(&self.data));
+    }
 }
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy, Pod, Zeroable)]
 pub struct GlobalUniforms {
+    /// Resolution the scene is rendered at.
     pub window_size: Vector2<f32>,
+    /// Resolution of the surface the render pass upsamples to.
+    pub surface_size: Vector2<f32>,
+    /// Size of the visible scene, in logical pixels.
+    pub view_size: Vector2<f32>,
+    /// See `Camera`.
+    pub camera_position: Vector2<f32>,
+    pub camera_zoom: f32,
+    pub camera_rotation: f32,
 }
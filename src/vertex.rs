@@ -1,7 +1,10 @@
+use std::collections::HashMap;
+use std::f32::consts::TAU;
+use std::fmt::{self, Display, Formatter};
 use std::mem::size_of;
 
 use bytemuck::{Pod, Zeroable};
-use nalgebra::{vector, Vector2};
+use nalgebra::{vector, Rotation2, Vector2};
 use palette::{Alpha, IntoColor, LinSrgb, LinSrgba};
 use wgpu::{vertex_attr_array, BufferAddress, VertexAttribute, VertexBufferLayout, VertexStepMode};
 
@@ -27,21 +30,241 @@ impl Vertex {
     }
 }
 
+/// Why `VertexList` can't build a shape. Nothing of the shape is added then.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShapeError {
+    TooFewSegments {
+        min: u32,
+        segments: u32,
+    },
+    /// Edges that cross or touch, or no area.
+    NotSimple,
+    /// Zero, parallel or infinite sides, or a line without thickness.
+    Degenerate,
+}
+
+impl Display for ShapeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ShapeError::TooFewSegments { min, segments } => {
+                write!(f, "segments must be at least {}, got {}", min, segments)
+            }
+            ShapeError::NotSimple => {
+                write!(
+                    f,
+                    "a polygon needs an area and edges that don't cross or touch"
+                )
+            }
+            ShapeError::Degenerate => write!(f, "the shape has no area"),
+        }
+    }
+}
+
+impl std::error::Error for ShapeError {}
+
+/// Sides `x` and `y` span an area, in either order.
+fn check_sides(x: Vector2<f32>, y: Vector2<f32>) -> Result<(), ShapeError> {
+    let area = cross(x, y);
+    if area != 0.0 && area.is_finite() {
+        Ok(())
+    } else {
+        Err(ShapeError::Degenerate)
+    }
+}
+
+fn check_segments(segments: u32, min: u32) -> Result<(), ShapeError> {
+    if segments >= min {
+        Ok(())
+    } else {
+        Err(ShapeError::TooFewSegments { min, segments })
+    }
+}
+
+/// Lines have round caps of half their thickness, which must be circles.
+fn check_thickness(thickness: f32) -> Result<(), ShapeError> {
+    let radius = thickness / 2.0;
+    if thickness > 0.0 {
+        check_sides(vector![radius, 0.0], vector![0.0, radius])
+    } else {
+        Err(ShapeError::Degenerate)
+    }
+}
+
+/// Segments of the round caps and joins of lines.
+const JOIN_SEGMENTS: u32 = 16;
+
+/// Makes vertices with the same colors. The emitted radiance shares its alpha with the albedo.
+fn painter(
+    albedo: impl IntoColor<LinSrgba>,
+    radiance: impl IntoColor<LinSrgb>,
+) -> impl Fn(Vector2<f32>, Vector2<f32>) -> Vertex {
+    let albedo = albedo.into_color();
+    let radiance = Alpha {
+        color: radiance.into_color(),
+        alpha: albedo.alpha,
+    };
+    move |position, normal| Vertex {
+        position,
+        normal,
+        albedo,
+        radiance,
+    }
+}
+
+/// The outward normal of the edge from `a` to `b` of a counterclockwise shape.
+fn edge_normal(a: Vector2<f32>, b: Vector2<f32>) -> Vector2<f32> {
+    let d = b - a;
+    vector![d.y, -d.x].normalize()
+}
+
+fn cross(a: Vector2<f32>, b: Vector2<f32>) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+/// Twice the signed area, positive when the points go counterclockwise.
+fn signed_area(points: &[Vector2<f32>]) -> f32 {
+    (0..points.len())
+        .map(|i| cross(points[i], points[(i + 1) % points.len()]))
+        .sum()
+}
+
+/// Whether the segments from `a` to `b` and from `c` to `d` cross or touch.
+fn segments_touch(a: Vector2<f32>, b: Vector2<f32>, c: Vector2<f32>, d: Vector2<f32>) -> bool {
+    let side = |p: Vector2<f32>, q: Vector2<f32>, r: Vector2<f32>| cross(q - p, r - p);
+    let opposite = |x: f32, y: f32| (x > 0.0 && y < 0.0) || (x < 0.0 && y > 0.0);
+    if opposite(side(c, d, a), side(c, d, b)) && opposite(side(a, b, c), side(a, b, d)) {
+        return true;
+    }
+    // Otherwise they only meet if an end lies on the other segment.
+    let on = |p: Vector2<f32>, q: Vector2<f32>, r: Vector2<f32>| {
+        side(p, q, r) == 0.0
+            && (p.x.min(q.x)..=p.x.max(q.x)).contains(&r.x)
+            && (p.y.min(q.y)..=p.y.max(q.y)).contains(&r.y)
+    };
+    on(c, d, a) || on(c, d, b) || on(a, b, c) || on(a, b, d)
+}
+
+/// Whether `polygon` can fill `points`: at least 3 corners, an area, and edges that only meet
+/// their neighbours at their shared corner.
+pub fn is_simple_polygon(points: &[Vector2<f32>]) -> bool {
+    let n = points.len();
+    if n < 3 || signed_area(points) == 0.0 {
+        return false;
+    }
+    let edge = |i: usize| (points[i], points[(i + 1) % n]);
+    let folds = |i: usize| {
+        let [a, b, c] = [i, i + 1, i + 2].map(|j| points[j % n]);
+        a == b || (cross(b - a, c - b) == 0.0 && (b - a).dot(&(c - b)) < 0.0)
+    };
+    (0..n).all(|i| !folds(i))
+        && (0..n).all(|i| {
+            let (a, b) = edge(i);
+            // Skip the neighbours, checked by `folds`.
+            let last = if i == 0 { n - 1 } else { n };
+            (i + 2..last).all(|j| {
+                let (c, d) = edge(j);
+                !segments_touch(a, b, c, d)
+            })
+        })
+}
+
+/// Splits a simple counterclockwise polygon into triangles by clipping ears, returning indices
+/// into `points`. Returns `None` if it runs out of ears, which only happens to polygons that
+/// aren't simple.
+fn triangulate(points: &[Vector2<f32>]) -> Option<Vec<[usize; 3]>> {
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut triangles = Vec::with_capacity(points.len().saturating_sub(2));
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let corner = |i: usize| {
+            [
+                remaining[(i + n - 1) % n],
+                remaining[i],
+                remaining[(i + 1) % n],
+            ]
+        };
+        let is_ear = |i: usize| {
+            let [a, b, c] = corner(i).map(|j| points[j]);
+            cross(b - a, c - b) > 0.0
+                && remaining.iter().all(|&j| {
+                    let p = points[j];
+                    // Points on the edges count as inside, except the corners themselves.
+                    corner(i).contains(&j)
+                        || cross(b - a, p - a) < 0.0
+                        || cross(c - b, p - b) < 0.0
+                        || cross(a - c, p - c) < 0.0
+                })
+        };
+        let ear = (0..n).find(|&i| is_ear(i))?;
+        triangles.push(corner(ear));
+        remaining.remove(ear);
+    }
+    if let [a, b, c] = remaining[..] {
+        triangles.push([a, b, c]);
+    }
+    Some(triangles)
+}
+
+/// The outward normal of the edge of a counterclockwise polygon closest to `p`.
+fn nearest_edge_normal(points: &[Vector2<f32>], p: Vector2<f32>) -> Vector2<f32> {
+    let n = points.len();
+    let distance = |i: usize| {
+        let (a, b) = (points[i], points[(i + 1) % n]);
+        let t = ((p - a).dot(&(b - a)) / (b - a).norm_squared()).clamp(0.0, 1.0);
+        (a + (b - a) * t - p).norm()
+    };
+    let nearest = (1..n).fold(0, |nearest, i| {
+        if distance(i) < distance(nearest) {
+            i
+        } else {
+            nearest
+        }
+    });
+    edge_normal(points[nearest], points[(nearest + 1) % n])
+}
+
+/// Indexed triangles. Vertices added by value are deduplicated, so triangles that share
+/// corners with the same attributes share vertices.
 #[derive(Debug, Clone)]
 pub struct VertexList {
-    triangles: Vec<[Vertex; 3]>,
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+    /// From the bits of each vertex to its index.
+    lookup: HashMap<[u32; 12], u32>,
 }
 
 impl VertexList {
     pub fn new() -> Self {
         Self {
-            triangles: Vec::new(),
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            lookup: HashMap::new(),
         }
     }
-    pub fn triangle(&mut self, triangle: [Vertex; 3]) -> &mut Self {
-        self.triangles.push(triangle);
+    /// Returns the index of `vertex`, adding it if no equal vertex is there yet.
+    pub fn vertex(&mut self, vertex: Vertex) -> u32 {
+        let vertices = &mut self.vertices;
+        *self
+            .lookup
+            .entry(bytemuck::cast(vertex))
+            .or_insert_with(|| {
+                vertices.push(vertex);
+                vertices.len() as u32 - 1
+            })
+    }
+    /// Adds a triangle of vertices returned by `vertex`.
+    pub fn indexed_triangle(&mut self, indices: [u32; 3]) -> &mut Self {
+        debug_assert!(indices.iter().all(|&i| (i as usize) < self.vertices.len()));
+        self.indices.extend(indices);
         self
     }
+    pub fn triangle(&mut self, triangle: [Vertex; 3]) -> &mut Self {
+        let indices = triangle.map(|vertex| self.vertex(vertex));
+        self.indexed_triangle(indices)
+    }
+    /// A triangle from the centre to every edge, with the normal of that edge. The centre has
+    /// no normal and is shared, so the normals shrink toward it. `x` and `y` can be in either
+    /// order, but not parallel.
     pub fn parallelogram(
         &mut self,
         c: Vector2<f32>,
@@ -49,43 +272,21 @@ impl VertexList {
         y: Vector2<f32>,
         albedo: impl IntoColor<LinSrgba>,
         radiance: impl IntoColor<LinSrgb>,
-    ) -> &mut Self {
-        let albedo = albedo.into_color();
-        let radiance = Alpha {
-            color: radiance.into_color(),
-            alpha: albedo.alpha,
-        };
+    ) -> Result<&mut Self, ShapeError> {
+        check_sides(x, y)?;
+        // The normals point out when y is counterclockwise from x, and -y spans the same shape.
+        let y = if cross(x, y) < 0.0 { -y } else { y };
+        let vertex = painter(albedo, radiance);
         let a = x + y;
         let b = x - y;
         let ny = vector![y.y, -y.x].normalize();
         let nx = vector![-x.y, x.x].normalize();
-        let tri = |a, b, normal| {
-            [
-                Vertex {
-                    position: c,
-                    normal,
-                    albedo,
-                    radiance,
-                },
-                Vertex {
-                    position: c + a,
-                    normal,
-                    albedo,
-                    radiance,
-                },
-                Vertex {
-                    position: c + b,
-                    normal,
-                    albedo,
-                    radiance,
-                },
-            ]
-        };
-        self.triangle(tri(b, a, ny))
-            .triangle(tri(a, -b, nx))
-            .triangle(tri(-b, -a, -ny))
-            .triangle(tri(-a, b, -nx));
-        self
+        let centre = self.vertex(vertex(c, Vector2::zeros()));
+        for (p, q, normal) in [(b, a, ny), (a, -b, nx), (-b, -a, -ny), (-a, b, -nx)] {
+            let [i, j] = [p, q].map(|corner| self.vertex(vertex(c + corner, normal)));
+            self.indexed_triangle([centre, i, j]);
+        }
+        Ok(self)
     }
     pub fn rectangle(
         &mut self,
@@ -93,13 +294,404 @@ impl VertexList {
         a: Vector2<f32>,
         albedo: impl IntoColor<LinSrgba>,
         radiance: impl IntoColor<LinSrgb>,
-    ) -> &mut Self {
+    ) -> Result<&mut Self, ShapeError> {
         self.parallelogram(c, vector![a.x, 0.0], vector![0.0, a.y], albedo, radiance)
     }
-    pub fn to_bytes(&self) -> &[u8] {
-        bytemuck::cast_slice(&self.triangles)
+    /// An ellipse through `c + x` and `c + y`, made of `segments` triangles around the centre.
+    /// The normals are perpendicular to the outline, so it emits like a smooth curve.
+    pub fn ellipse(
+        &mut self,
+        c: Vector2<f32>,
+        x: Vector2<f32>,
+        y: Vector2<f32>,
+        segments: u32,
+        albedo: impl IntoColor<LinSrgba>,
+        radiance: impl IntoColor<LinSrgb>,
+    ) -> Result<&mut Self, ShapeError> {
+        check_segments(segments, 3)?;
+        check_sides(x, y)?;
+        let vertex = painter(albedo, radiance);
+        // Turn the tangent clockwise to point outward, or the other way if x and y are mirrored.
+        let outward = cross(x, y).signum();
+        let normal = |angle: f32| {
+            let tangent = y * angle.cos() - x * angle.sin();
+            vector![tangent.y, -tangent.x].normalize() * outward
+        };
+        let angle = |i: f32| i / segments as f32 * TAU;
+        let rim: Vec<u32> = (0..segments)
+            .map(|i| {
+                let angle = angle(i as f32);
+                self.vertex(vertex(c + x * angle.cos() + y * angle.sin(), normal(angle)))
+            })
+            .collect();
+        for i in 0..segments as usize {
+            // The centre takes the normal of the middle of each segment, so the interpolated
+            // normals keep about the same length.
+            let centre = self.vertex(vertex(c, normal(angle(i as f32 + 0.5))));
+            self.indexed_triangle([centre, rim[i], rim[(i + 1) % rim.len()]]);
+        }
+        Ok(self)
+    }
+    pub fn circle(
+        &mut self,
+        c: Vector2<f32>,
+        radius: f32,
+        segments: u32,
+        albedo: impl IntoColor<LinSrgba>,
+        radiance: impl IntoColor<LinSrgb>,
+    ) -> Result<&mut Self, ShapeError> {
+        self.ellipse(
+            c,
+            vector![radius, 0.0],
+            vector![0.0, radius],
+            segments,
+            albedo,
+            radiance,
+        )
+    }
+    /// A polygon that `is_simple_polygon`, convex or not, in either winding. Every edge gets its
+    /// outward normal, and the inside the normal of the nearest edge.
+    pub fn polygon(
+        &mut self,
+        points: &[Vector2<f32>],
+        albedo: impl IntoColor<LinSrgba>,
+        radiance: impl IntoColor<LinSrgb>,
+    ) -> Result<&mut Self, ShapeError> {
+        if !is_simple_polygon(points) {
+            return Err(ShapeError::NotSimple);
+        }
+        let vertex = painter(albedo, radiance);
+        let mut points = points.to_vec();
+        if signed_area(&points) < 0.0 {
+            points.reverse();
+        }
+        let n = points.len();
+        let convex = (0..n).all(|i| {
+            let [a, b, c] = [i, i + 1, i + 2].map(|j| points[j % n]);
+            cross(b - a, c - b) >= 0.0
+        });
+        if convex {
+            // A triangle from the centre to every edge, with its normal.
+            let centre = points.iter().sum::<Vector2<f32>>() / n as f32;
+            for i in 0..n {
+                let (a, b) = (points[i], points[(i + 1) % n]);
+                let normal = edge_normal(a, b);
+                self.triangle([vertex(centre, normal), vertex(a, normal), vertex(b, normal)]);
+            }
+        } else {
+            // Split every triangle at its centroid, so each polygon edge has a triangle of its
+            // own. The ones along diagonals take the normal of the nearest edge, like the parts
+            // of a convex polygon. Simple polygons always have an ear, so triangulating only
+            // fails on rounding.
+            let triangles = triangulate(&points).ok_or(ShapeError::NotSimple)?;
+            for [i, j, k] in triangles {
+                let centroid = (points[i] + points[j] + points[k]) / 3.0;
+                for (a, b) in [(i, j), (j, k), (k, i)] {
+                    let normal = if (a + 1) % n == b {
+                        edge_normal(points[a], points[b])
+                    } else {
+                        let middle = (centroid + points[a] + points[b]) / 3.0;
+                        nearest_edge_normal(&points, middle)
+                    };
+                    self.triangle([
+                        vertex(centroid, normal),
+                        vertex(points[a], normal),
+                        vertex(points[b], normal),
+                    ]);
+                }
+            }
+        }
+        Ok(self)
+    }
+    /// The part of a line from `a` to `b`, offset along `na` at `a` and `nb` at `b`. Each side
+    /// has the normals pointing its way.
+    fn band(
+        &mut self,
+        (a, na): (Vector2<f32>, Vector2<f32>),
+        (b, nb): (Vector2<f32>, Vector2<f32>),
+        half_thickness: f32,
+        vertex: &impl Fn(Vector2<f32>, Vector2<f32>) -> Vertex,
+    ) {
+        for side in [1.0, -1.0] {
+            let (na, nb) = (na * side, nb * side);
+            let [i, j, k, l] = [
+                vertex(a, na),
+                vertex(b, nb),
+                vertex(b + nb * half_thickness, nb),
+                vertex(a + na * half_thickness, na),
+            ]
+            .map(|v| self.vertex(v));
+            self.indexed_triangle([i, j, k]).indexed_triangle([i, k, l]);
+        }
+    }
+    /// A line through `points`, `thickness` wide, with round caps and joins.
+    pub fn polyline(
+        &mut self,
+        points: &[Vector2<f32>],
+        thickness: f32,
+        albedo: impl IntoColor<LinSrgba>,
+        radiance: impl IntoColor<LinSrgb>,
+    ) -> Result<&mut Self, ShapeError> {
+        check_thickness(thickness)?;
+        let albedo: LinSrgba = albedo.into_color();
+        let radiance: LinSrgb = radiance.into_color();
+        let vertex = painter(albedo, radiance);
+        let half_thickness = thickness / 2.0;
+        // The round parts go first, so the straight parts are drawn over them where they overlap.
+        for &point in points {
+            self.circle(point, half_thickness, JOIN_SEGMENTS, albedo, radiance)?;
+        }
+        for pair in points.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            if a != b {
+                let normal = edge_normal(a, b);
+                self.band((a, normal), (b, normal), half_thickness, &vertex);
+            }
+        }
+        Ok(self)
+    }
+    /// A line along the circle of `radius` around `c`, counterclockwise from the angle `start` to
+    /// `end` in radians, with round caps. The normals follow the circle.
+    #[allow(clippy::too_many_arguments)]
+    pub fn arc(
+        &mut self,
+        c: Vector2<f32>,
+        radius: f32,
+        start: f32,
+        end: f32,
+        thickness: f32,
+        segments: u32,
+        albedo: impl IntoColor<LinSrgba>,
+        radiance: impl IntoColor<LinSrgb>,
+    ) -> Result<&mut Self, ShapeError> {
+        check_segments(segments, 1)?;
+        check_thickness(thickness)?;
+        let albedo: LinSrgba = albedo.into_color();
+        let radiance: LinSrgb = radiance.into_color();
+        let vertex = painter(albedo, radiance);
+        let half_thickness = thickness / 2.0;
+        let points: Vec<_> = (0..=segments)
+            .map(|i| {
+                let angle = start + (end - start) * i as f32 / segments as f32;
+                let normal = Rotation2::new(angle) * Vector2::x();
+                (c + normal * radius, normal)
+            })
+            .collect();
+        for (point, _) in [points[0], points[segments as usize]] {
+            self.circle(point, half_thickness, JOIN_SEGMENTS, albedo, radiance)?;
+        }
+        for pair in points.windows(2) {
+            self.band(pair[0], pair[1], half_thickness, &vertex);
+        }
+        Ok(self)
+    }
+    /// Drops what `vertex` needs to find equal vertices, once nothing more is added. Vertices
+    /// added afterwards are not merged with the earlier ones.
+    pub fn finish(mut self) -> Self {
+        self.lookup = HashMap::new();
+        self
     }
+    pub fn vertex_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(&self.vertices)
+    }
+    pub fn index_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(&self.indices)
+    }
+    pub fn vertex_count(&self) -> u32 {
+        self.vertices.len() as u32
+    }
+    /// The number of indices, three per triangle.
     pub fn len(&self) -> u32 {
-        self.triangles.len() as u32 * 3
+        self.indices.len() as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(points: &[[f32; 2]]) -> Vec<Vector2<f32>> {
+        points.iter().map(|&point| point.into()).collect()
+    }
+
+    /// An L with its inner corner at (1, 1), counterclockwise.
+    fn l_shape() -> Vec<Vector2<f32>> {
+        points(&[
+            [0.0, 0.0],
+            [2.0, 0.0],
+            [2.0, 1.0],
+            [1.0, 1.0],
+            [1.0, 2.0],
+            [0.0, 2.0],
+        ])
+    }
+
+    #[test]
+    fn triangulate_covers_concave_polygons() {
+        let points = l_shape();
+        let triangles = triangulate(&points).unwrap();
+        assert_eq!(triangles.len(), points.len() - 2);
+        let area: f32 = triangles
+            .iter()
+            .map(|triangle| signed_area(&triangle.map(|i| points[i])))
+            .sum();
+        assert_eq!(area, signed_area(&points));
+        // No triangle is flipped.
+        assert!(triangles
+            .iter()
+            .all(|triangle| signed_area(&triangle.map(|i| points[i])) > 0.0));
+    }
+
+    #[test]
+    fn triangulate_fails_without_ears() {
+        // Going clockwise, every corner bends the wrong way.
+        let clockwise = points(&[[0.0, 0.0], [0.0, 1.0], [1.0, 1.0], [1.0, 0.0]]);
+        assert_eq!(triangulate(&clockwise), None);
+    }
+
+    #[test]
+    fn simple_polygons() {
+        assert!(is_simple_polygon(&l_shape()));
+        assert!(is_simple_polygon(&points(&[[0.0, 0.0], [0.0, 1.0], [
+            1.0, 0.0
+        ]])));
+        // Too few corners, no area, a repeated corner.
+        assert!(!is_simple_polygon(&points(&[[0.0, 0.0], [1.0, 0.0]])));
+        assert!(!is_simple_polygon(&points(&[[0.0, 0.0], [1.0, 0.0], [
+            2.0, 0.0
+        ]])));
+        assert!(!is_simple_polygon(&points(&[
+            [0.0, 0.0],
+            [1.0, 0.0],
+            [1.0, 0.0],
+            [0.0, 1.0]
+        ])));
+        // A bowtie, a figure eight, and a corner touching the opposite edge.
+        assert!(!is_simple_polygon(&points(&[
+            [0.0, 0.0],
+            [1.0, 1.0],
+            [1.0, 0.0],
+            [0.0, 1.0]
+        ])));
+        assert!(!is_simple_polygon(&points(&[
+            [0.0, 0.0],
+            [4.0, 0.0],
+            [4.0, 1.0],
+            [2.0, -1.0],
+            [0.0, 1.0],
+            [-2.0, -1.0]
+        ])));
+        assert!(!is_simple_polygon(&points(&[
+            [0.0, 0.0],
+            [2.0, 0.0],
+            [2.0, 2.0],
+            [1.0, 0.0],
+            [0.0, 2.0]
+        ])));
+        // An edge folding back onto the previous one.
+        assert!(!is_simple_polygon(&points(&[
+            [0.0, 0.0],
+            [2.0, 0.0],
+            [1.0, 0.0],
+            [1.0, 1.0]
+        ])));
+    }
+
+    #[test]
+    fn rectangles_share_their_centre() {
+        let mut vertices = VertexList::new();
+        vertices
+            .rectangle(
+                vector![0.0, 0.0],
+                vector![2.0, 1.0],
+                LinSrgba::new(1.0, 1.0, 1.0, 1.0),
+                LinSrgb::new(0.0, 0.0, 0.0),
+            )
+            .unwrap();
+        // The centre, and both ends of each edge with its normal.
+        assert_eq!(vertices.vertex_count(), 9);
+        assert_eq!(vertices.len(), 12);
+        let vertices = vertices.finish();
+        assert!(vertices.lookup.is_empty());
+        assert_eq!(vertices.vertex_count(), 9);
+    }
+
+    #[test]
+    fn concave_polygons_have_normals_everywhere() {
+        let mut vertices = VertexList::new();
+        vertices
+            .polygon(
+                &l_shape(),
+                LinSrgba::new(1.0, 1.0, 1.0, 1.0),
+                LinSrgb::new(0.0, 0.0, 0.0),
+            )
+            .unwrap();
+        for vertex in &vertices.vertices {
+            assert!((vertex.normal.norm() - 1.0).abs() < 1e-6, "{:?}", vertex);
+            // Every normal points out of the L, along an axis.
+            assert!(vertex.normal.x == 0.0 || vertex.normal.y == 0.0);
+        }
+    }
+
+    #[test]
+    fn invalid_shapes_add_nothing() {
+        let white = LinSrgba::new(1.0, 1.0, 1.0, 1.0);
+        let black = LinSrgb::new(0.0, 0.0, 0.0);
+        let c = Vector2::zeros();
+        let x = vector![1.0, 0.0];
+        let mut vertices = VertexList::new();
+        assert_eq!(
+            vertices.parallelogram(c, x, x * 2.0, white, black).err(),
+            Some(ShapeError::Degenerate)
+        );
+        assert_eq!(
+            vertices.rectangle(c, vector![1.0, 0.0], white, black).err(),
+            Some(ShapeError::Degenerate)
+        );
+        assert_eq!(
+            vertices.ellipse(c, x, Vector2::y(), 2, white, black).err(),
+            Some(ShapeError::TooFewSegments {
+                min: 3,
+                segments: 2
+            })
+        );
+        assert_eq!(
+            vertices.circle(c, 0.0, 8, white, black).err(),
+            Some(ShapeError::Degenerate)
+        );
+        let bowtie = points(&[[0.0, 0.0], [1.0, 1.0], [1.0, 0.0], [0.0, 1.0]]);
+        assert_eq!(
+            vertices.polygon(&bowtie, white, black).err(),
+            Some(ShapeError::NotSimple)
+        );
+        assert_eq!(
+            vertices.polyline(&l_shape(), 0.0, white, black).err(),
+            Some(ShapeError::Degenerate)
+        );
+        assert_eq!(
+            vertices.arc(c, 1.0, 0.0, 1.0, 0.1, 0, white, black).err(),
+            Some(ShapeError::TooFewSegments {
+                min: 1,
+                segments: 0
+            })
+        );
+        assert_eq!(vertices.vertex_count(), 0);
+        assert_eq!(vertices.len(), 0);
+    }
+
+    #[test]
+    fn parallelograms_point_out_either_way() {
+        let white = LinSrgba::new(1.0, 1.0, 1.0, 1.0);
+        let black = LinSrgb::new(0.0, 0.0, 0.0);
+        for half_size in [vector![2.0, 1.0], vector![2.0, -1.0], vector![-2.0, -1.0]] {
+            let mut vertices = VertexList::new();
+            vertices
+                .rectangle(Vector2::zeros(), half_size, white, black)
+                .unwrap();
+            for vertex in &vertices.vertices {
+                // The corners are away from the centre, along their outward normal.
+                assert!(vertex.position.dot(&vertex.normal) >= 0.0, "{:?}", vertex);
+            }
+        }
     }
 }
@@ -0,0 +1,72 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::scene::{Scene, SceneError};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Polls the modification times of a scene file and the images it loads, reloading the scene
+/// when any of them changes.
+#[derive(Debug)]
+pub struct SceneWatcher {
+    path: PathBuf,
+    curve_tolerance: f32,
+    /// The scene file and its images, with their last seen modification times.
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    last_poll: Instant,
+}
+
+impl SceneWatcher {
+    /// Watches the files `scene` was loaded from, and reloads with `curve_tolerance` like
+    /// `Scene::load`.
+    pub fn new(path: impl Into<PathBuf>, scene: &Scene, curve_tolerance: f32) -> Self {
+        let path = path.into();
+        let files = Self::files(&path, scene);
+        Self {
+            path,
+            curve_tolerance,
+            files,
+            last_poll: Instant::now(),
+        }
+    }
+
+    fn files(path: &Path, scene: &Scene) -> Vec<(PathBuf, Option<SystemTime>)> {
+        std::iter::once(path.to_path_buf())
+            .chain(scene.image_paths(path))
+            .map(|path| {
+                let modified = Self::modified(&path);
+                (path, modified)
+            })
+            .collect()
+    }
+
+    fn modified(path: &Path) -> Option<SystemTime> {
+        std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    /// Returns the reloaded scene if a file changed since the last call.
+    pub fn poll(&mut self) -> Option<Result<Scene, SceneError>> {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return None;
+        }
+        self.last_poll = Instant::now();
+
+        let mut changed = false;
+        for (path, last_modified) in &mut self.files {
+            let modified = Self::modified(path);
+            changed |= modified.is_some() && modified != *last_modified;
+            *last_modified = modified;
+        }
+        if !changed {
+            return None;
+        }
+        let scene = Scene::load(&self.path, self.curve_tolerance);
+        // The scene may now load other images. If it failed, keep watching the old ones.
+        if let Ok(scene) = &scene {
+            self.files = Self::files(&self.path, scene);
+        }
+        Some(scene)
+    }
+}